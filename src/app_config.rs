@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::key_layout::{KeyLayout, format_note_keys, key_name, parse_note_keys};
+use crate::timing::{InstrumentPreset, TimingProfile};
+use crate::{AppState, MAX_SPEED_LIMIT, clamp_speed};
+
+// A song file opened before, listed in the library panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentSong {
+    pub path: String,
+    pub name: String,
+    pub pinned: bool,
+}
+
+// General settings persisted next to hotkeys.json
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub speed: f32,
+    pub max_speed: f32,
+    pub song_path: Option<String>,
+    pub manual_mode: bool,
+    pub loop_enabled: bool,
+    pub auto_play: bool,
+    pub minimize_on_play: bool,
+    pub show_overlay: bool,
+    pub always_on_top: bool,
+    pub finish_alert: bool,
+    pub hold_last_chord: bool,
+    pub sustain_chords: bool,
+    pub lead_in_secs: u32,
+    pub expressive_timing: bool,
+    pub window_size: Option<[f32; 2]>,
+    pub humanize_seed: Option<u64>,
+    pub trim_lead_in: bool,
+    pub dedupe_notes: bool,
+    pub time_offset_ms: i64,
+    pub manual_hold: bool,
+    pub target_window: String,
+    pub instrument_preset: InstrumentPreset,
+    pub timing: TimingProfile,
+    pub legato: f32,
+    pub repeat_gap_ms: u64,
+    pub timing_jitter_ms: u64,
+    pub min_hold_ms: u64,
+    pub debug_logging: bool,
+    pub key_layout: KeyLayout,
+    // Characters, or names like "Enter"; older files saved plain characters, which
+    // read the same way
+    pub custom_keys: Vec<String>,
+    pub recent_songs: Vec<RecentSong>,
+    pub playlist: Vec<(PathBuf, usize)>,
+    pub playlist_mode: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            max_speed: 2.0,
+            song_path: None,
+            manual_mode: false,
+            loop_enabled: false,
+            auto_play: false,
+            minimize_on_play: false,
+            show_overlay: false,
+            always_on_top: false,
+            finish_alert: false,
+            hold_last_chord: false,
+            sustain_chords: false,
+            lead_in_secs: 3,
+            expressive_timing: true,
+            window_size: None,
+            humanize_seed: None,
+            trim_lead_in: false,
+            dedupe_notes: true,
+            time_offset_ms: 0,
+            manual_hold: false,
+            target_window: String::new(),
+            instrument_preset: InstrumentPreset::default(),
+            timing: TimingProfile::default(),
+            legato: 0.0,
+            repeat_gap_ms: 15,
+            timing_jitter_ms: 0,
+            min_hold_ms: 10,
+            debug_logging: false,
+            key_layout: KeyLayout::default(),
+            custom_keys: KeyLayout::Custom.note_keys().map(key_name).to_vec(),
+            recent_songs: Vec::new(),
+            playlist: Vec::new(),
+            playlist_mode: false,
+        }
+    }
+}
+
+impl From<&AppState> for AppConfig {
+    fn from(state: &AppState) -> Self {
+        Self {
+            speed: state.speed,
+            max_speed: state.max_speed,
+            song_path: state.song_path.clone(),
+            manual_mode: state.manual_mode,
+            loop_enabled: state.loop_enabled,
+            auto_play: state.auto_play,
+            minimize_on_play: state.minimize_on_play,
+            show_overlay: state.show_overlay,
+            always_on_top: state.always_on_top,
+            finish_alert: state.finish_alert,
+            hold_last_chord: state.hold_last_chord,
+            sustain_chords: state.sustain_chords,
+            lead_in_secs: state.lead_in_secs,
+            expressive_timing: state.expressive_timing,
+            window_size: state.window_size,
+            humanize_seed: state.humanize_seed,
+            trim_lead_in: state.trim_lead_in,
+            dedupe_notes: state.dedupe_notes,
+            time_offset_ms: state.time_offset_ms,
+            manual_hold: state.manual_hold,
+            target_window: state.target_window.clone(),
+            instrument_preset: state.instrument_preset,
+            timing: state.timing,
+            legato: state.legato,
+            repeat_gap_ms: state.repeat_gap_ms,
+            timing_jitter_ms: state.timing_jitter_ms,
+            min_hold_ms: state.min_hold_ms,
+            debug_logging: state.debug_logging,
+            key_layout: state.key_layout,
+            custom_keys: state.custom_keys.map(key_name).to_vec(),
+            recent_songs: state.recent_songs.clone(),
+            playlist: state.playlist.clone(),
+            playlist_mode: state.playlist_mode,
+        }
+    }
+}
+
+impl AppConfig {
+    // Returns a warning when part of the file had to be ignored
+    pub fn apply(self, state: &mut AppState) -> Option<String> {
+        state.max_speed = self.max_speed.clamp(2.0, MAX_SPEED_LIMIT);
+        state.speed = clamp_speed(state, self.speed);
+        state.song_path = self.song_path;
+        state.manual_mode = self.manual_mode;
+        state.loop_enabled = self.loop_enabled;
+        state.auto_play = self.auto_play;
+        state.minimize_on_play = self.minimize_on_play;
+        state.show_overlay = self.show_overlay;
+        state.always_on_top = self.always_on_top;
+        state.finish_alert = self.finish_alert;
+        state.hold_last_chord = self.hold_last_chord;
+        state.sustain_chords = self.sustain_chords;
+        state.lead_in_secs = self.lead_in_secs.min(10);
+        state.expressive_timing = self.expressive_timing;
+        state.window_size = self.window_size;
+        state.humanize_seed = self.humanize_seed;
+        state.trim_lead_in = self.trim_lead_in;
+        state.dedupe_notes = self.dedupe_notes;
+        state.time_offset_ms = self.time_offset_ms.clamp(-10_000, 10_000);
+        state.manual_hold = self.manual_hold;
+        state.target_window = self.target_window;
+        state.instrument_preset = self.instrument_preset;
+        state.debug_logging = self.debug_logging;
+        state.key_layout = self.key_layout;
+        state.recent_songs = self.recent_songs;
+        state.playlist = self.playlist;
+        state.playlist_mode = self.playlist_mode;
+        state.timing = self.instrument_preset.profile().unwrap_or(self.timing);
+        state.legato = self.legato.clamp(0.0, 1.0);
+        state.repeat_gap_ms = self.repeat_gap_ms.min(100);
+        state.timing_jitter_ms = self.timing_jitter_ms.min(50);
+        state.min_hold_ms = self.min_hold_ms.min(100);
+        let warning = match parse_note_keys(&self.custom_keys.join(" ")) {
+            Ok(keys) => {
+                state.custom_keys = keys;
+                None
+            }
+            Err(e) => {
+                state.custom_keys = KeyLayout::Custom.note_keys();
+                Some(format!("{}; using the default keys", e))
+            }
+        };
+        state.custom_keys_text = format_note_keys(&state.custom_keys);
+        warning
+    }
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    let config_dir =
+        dirs::config_dir().ok_or_else(|| "Could not find config directory".to_string())?;
+    Ok(config_dir.join("sky_sheet_player").join("settings.json"))
+}
+
+pub fn save_config(config: &AppConfig) -> Result<(), String> {
+    let config_path = config_path()?;
+
+    // Create directory if it doesn't exist
+    if let Some(app_config_dir) = config_path.parent() {
+        std::fs::create_dir_all(app_config_dir)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(config)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    let mut file =
+        File::create(config_path).map_err(|e| format!("Failed to create settings file: {}", e))?;
+    file.write_all(json.as_bytes())
+        .map_err(|e| format!("Failed to write settings file: {}", e))?;
+
+    Ok(())
+}
+
+// Forgets the saved settings so the defaults apply from now on
+pub fn delete_config() -> Result<(), String> {
+    match std::fs::remove_file(config_path()?) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to delete settings file: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Always yields usable settings; the second value explains any fallback to defaults
+pub fn load_config() -> (AppConfig, Option<String>) {
+    match read_config() {
+        Ok(config) => (config, None),
+        Err(e) => {
+            eprintln!("Settings warning: {}", e);
+            (AppConfig::default(), Some(e))
+        }
+    }
+}
+
+fn read_config() -> Result<AppConfig, String> {
+    let config_path = config_path()?;
+    if !config_path.exists() {
+        return Ok(AppConfig::default());
+    }
+
+    let mut file =
+        File::open(&config_path).map_err(|e| format!("Failed to open settings file: {}", e))?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .map_err(|e| format!("Failed to read settings file: {}", e))?;
+
+    serde_json::from_str(&contents).map_err(|e| format!("Failed to parse settings file: {}", e))
+}
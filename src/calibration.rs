@@ -0,0 +1,79 @@
+use enigo::Key;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::key_output::KeyOutput;
+
+// A steady pattern of taps, spaced like a fast passage
+const TAPS: u32 = 40;
+const INTERVAL: Duration = Duration::from_millis(25);
+// Shift does nothing on its own, so the taps are harmless whichever window has focus
+const PROBE_KEY: Key = Key::Shift;
+// Never suggest a hold shorter than the default
+const MIN_SUGGESTED_HOLD_MS: u64 = 10;
+
+// How the keyboard output kept up with the pattern
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LatencyReport {
+    // Time spent inside each press or release call
+    pub call_avg: Duration,
+    pub call_max: Duration,
+    // How long after its intended time each press went out
+    pub late_avg: Duration,
+    pub late_max: Duration,
+}
+
+impl LatencyReport {
+    // A hold that covers the worst call plus the worst delay, so a press that goes
+    // out late still stays down long enough to register
+    pub fn suggested_min_hold_ms(&self) -> u64 {
+        let worst = (self.call_max + self.late_max).as_secs_f32() * 1000.0;
+        (worst.ceil() as u64).max(MIN_SUGGESTED_HOLD_MS)
+    }
+}
+
+impl fmt::Display for LatencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        write!(
+            f,
+            "Key calls take {:.2} ms on average (max {:.2} ms); presses go out {:.2} ms late \
+             on average (max {:.2} ms)",
+            ms(self.call_avg),
+            ms(self.call_max),
+            ms(self.late_avg),
+            ms(self.late_max)
+        )
+    }
+}
+
+// Taps the probe key on a fixed schedule, timing every call. Blocks for about a second.
+pub fn measure(output: &mut dyn KeyOutput) -> LatencyReport {
+    let mut report = LatencyReport::default();
+    let mut call_total = Duration::ZERO;
+    let mut late_total = Duration::ZERO;
+
+    let start = Instant::now();
+    for tap in 0..TAPS {
+        let intended = start + INTERVAL * tap;
+        if let Some(wait) = intended.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        let late = Instant::now().saturating_duration_since(intended);
+        report.late_max = report.late_max.max(late);
+        late_total += late;
+
+        let started = Instant::now();
+        output.press(PROBE_KEY);
+        let pressed = Instant::now();
+        output.release(PROBE_KEY);
+        for took in [pressed - started, pressed.elapsed()] {
+            report.call_max = report.call_max.max(took);
+            call_total += took;
+        }
+    }
+    report.call_avg = call_total / (TAPS * 2);
+    report.late_avg = late_total / TAPS;
+    report
+}
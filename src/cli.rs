@@ -0,0 +1,219 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::{AppState, MAX_SPEED_LIMIT, MIN_SPEED, initial_state, open_song_now, play_song_gui};
+
+pub const USAGE: &str =
+    "Usage: sky_sheet_player [--play <file> [--speed <0.5-4.0>] [--lead-in <seconds>]]";
+
+// Options for playing a song straight from the command line, without the window
+#[derive(Debug)]
+pub struct CliOptions {
+    pub play: String,
+    pub speed: Option<f32>,
+    pub lead_in: Option<u32>,
+}
+
+// What the command line asked for
+#[derive(Debug)]
+pub enum CliAction {
+    // No song was given, so the GUI opens as usual
+    Gui,
+    Help,
+    Play(CliOptions),
+}
+
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<CliAction, String> {
+    let mut play = None;
+    let mut speed = None;
+    let mut lead_in = None;
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} needs a value\n{}", name, USAGE))
+        };
+        match arg.as_str() {
+            "--play" => play = Some(value("--play")?),
+            "--speed" => {
+                let text = value("--speed")?;
+                let parsed: f32 = text
+                    .parse()
+                    .map_err(|_| format!("Invalid speed: {}", text))?;
+                if !(MIN_SPEED..=MAX_SPEED_LIMIT).contains(&parsed) {
+                    return Err(format!(
+                        "Speed must be between {} and {}, got {}",
+                        MIN_SPEED, MAX_SPEED_LIMIT, text
+                    ));
+                }
+                speed = Some(parsed);
+            }
+            "--lead-in" => {
+                let text = value("--lead-in")?;
+                let parsed: u32 = text
+                    .parse()
+                    .map_err(|_| format!("Invalid lead-in: {}", text))?;
+                lead_in = Some(parsed.min(10));
+            }
+            "--help" | "-h" => return Ok(CliAction::Help),
+            other => return Err(format!("Unknown argument: {}\n{}", other, USAGE)),
+        }
+    }
+    match play {
+        Some(play) => Ok(CliAction::Play(CliOptions {
+            play,
+            speed,
+            lead_in,
+        })),
+        None if speed.is_some() || lead_in.is_some() => {
+            Err(format!("--speed and --lead-in need --play\n{}", USAGE))
+        }
+        None => Ok(CliAction::Gui),
+    }
+}
+
+// Plays one song with the saved settings and reports progress on stdout
+pub fn run(options: CliOptions) -> Result<(), String> {
+    let mut state = initial_state();
+    prepare(&mut state, &options)?;
+    println!(
+        "Playing {} at {:.2}x",
+        state.songs[state.active_song].name, state.speed
+    );
+
+    let state = Arc::new(Mutex::new(state));
+    let worker = {
+        let state = Arc::clone(&state);
+        thread::spawn(move || play_song_gui(state))
+    };
+
+    let mut last_status = String::new();
+    let mut last_decile = None;
+    while !worker.is_finished() {
+        report(&state.lock().unwrap(), &mut last_status, &mut last_decile);
+        thread::sleep(Duration::from_millis(100));
+    }
+    worker
+        .join()
+        .map_err(|_| "Playback stopped unexpectedly".to_string())?;
+    report(&state.lock().unwrap(), &mut last_status, &mut last_decile);
+    Ok(())
+}
+
+// Opens the requested song and turns off anything that would play something else
+fn prepare(state: &mut AppState, options: &CliOptions) -> Result<(), String> {
+    // A script expects the run to end, and there's no one to tap notes
+    state.manual_mode = false;
+    state.loop_enabled = false;
+    // A saved playlist or medley would otherwise play instead of the given file
+    state.playlist_mode = false;
+    state.medley_mode = false;
+    open_song_now(state, &options.play);
+    if state.songs.is_empty() {
+        return Err(state.status.clone());
+    }
+    if let Some(speed) = options.speed {
+        // Asking for a speed outright overrides the saved maximum for this run
+        state.max_speed = state.max_speed.max(speed);
+        state.speed = speed;
+    }
+    if let Some(lead_in) = options.lead_in {
+        state.lead_in_secs = lead_in;
+    }
+    Ok(())
+}
+
+// Prints status changes and every tenth of the way through the song
+fn report(state: &AppState, last_status: &mut String, last_decile: &mut Option<usize>) {
+    if state.status != *last_status {
+        println!("{}", state.status);
+        *last_status = state.status.clone();
+    }
+    if let Some(decile) = (state.progress * 10).checked_div(state.total)
+        && *last_decile != Some(decile)
+    {
+        println!(
+            "{}% ({}/{} notes)",
+            decile * 10,
+            state.progress,
+            state.total
+        );
+        *last_decile = Some(decile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plays_several_songs;
+    use crate::test_support::{TempDir, song};
+
+    fn parse(args: &[&str]) -> Result<CliAction, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_open_the_gui() {
+        assert!(matches!(parse(&[]), Ok(CliAction::Gui)));
+    }
+
+    #[test]
+    fn help_is_not_an_error() {
+        assert!(matches!(parse(&["--help"]), Ok(CliAction::Help)));
+        assert!(matches!(parse(&["-h"]), Ok(CliAction::Help)));
+    }
+
+    #[test]
+    fn play_takes_speed_and_a_capped_lead_in() {
+        let Ok(CliAction::Play(options)) =
+            parse(&["--play", "song.txt", "--speed", "1.5", "--lead-in", "30"])
+        else {
+            panic!("expected a song to play");
+        };
+        assert_eq!(options.play, "song.txt");
+        assert_eq!(options.speed, Some(1.5));
+        assert_eq!(options.lead_in, Some(10));
+    }
+
+    #[test]
+    fn speed_outside_the_range_is_rejected() {
+        for speed in ["0.4", "4.5"] {
+            let error = parse(&["--play", "song.txt", "--speed", speed]).unwrap_err();
+            assert!(error.starts_with("Speed must be between"), "{}", error);
+        }
+        assert!(parse(&["--play", "song.txt", "--speed", "4.0"]).is_ok());
+    }
+
+    #[test]
+    fn speed_without_play_is_rejected() {
+        let error = parse(&["--speed", "1.5"]).unwrap_err();
+        assert!(error.starts_with("--speed and --lead-in need --play"));
+    }
+
+    #[test]
+    fn unknown_arguments_are_rejected() {
+        let error = parse(&["--play", "song.txt", "--loud"]).unwrap_err();
+        assert!(error.starts_with("Unknown argument: --loud"));
+    }
+
+    #[test]
+    fn saved_playlist_does_not_take_over_play() {
+        let dir = TempDir::new("cli_playlist");
+        let path = dir.path().join("song.json");
+        std::fs::write(&path, serde_json::to_string(&[song(&[(0, 0)])]).unwrap()).unwrap();
+        let mut state = AppState {
+            playlist: vec![(dir.path().join("other.json"), 0)],
+            playlist_mode: true,
+            medley_mode: true,
+            ..Default::default()
+        };
+        let options = CliOptions {
+            play: path.to_string_lossy().into_owned(),
+            speed: None,
+            lead_in: None,
+        };
+        prepare(&mut state, &options).unwrap();
+        assert!(!plays_several_songs(&state));
+        assert_eq!(state.song_path.as_deref(), Some(options.play.as_str()));
+    }
+}
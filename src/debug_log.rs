@@ -0,0 +1,132 @@
+// Optional timestamped playback log for bug reports, written to the config directory
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Older log is kept as debug.log.1 once the current one grows past this
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static WRITER: Mutex<Option<Log>> = Mutex::new(None);
+
+// The open log file and how big it has grown, so a long session still rotates
+struct Log {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    written: u64,
+}
+
+impl Log {
+    // Moves an oversized log aside first, then appends to the file at `path`
+    fn open(path: &Path) -> Result<Self, String> {
+        if std::fs::metadata(path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+            let _ = std::fs::rename(path, path.with_extension("log.1"));
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open debug log: {}", e))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            written,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) {
+        if writeln!(self.writer, "{}", line).is_ok() {
+            self.written += line.len() as u64 + 1;
+        }
+    }
+
+    // Flushes, then starts a fresh file if this one went past the limit
+    fn flush(&mut self) -> Result<(), String> {
+        let _ = self.writer.flush();
+        if self.written > MAX_LOG_BYTES {
+            *self = Self::open(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn set_enabled(enabled: bool) -> Result<(), String> {
+    let mut writer = WRITER.lock().unwrap();
+    if let Some(mut old) = writer.take() {
+        let _ = old.writer.flush();
+    }
+    ENABLED.store(false, Ordering::Relaxed);
+    if !enabled {
+        return Ok(());
+    }
+
+    let config_dir =
+        dirs::config_dir().ok_or_else(|| "Could not find config directory".to_string())?;
+    let app_config_dir = config_dir.join("sky_sheet_player");
+    std::fs::create_dir_all(&app_config_dir)
+        .map_err(|e| format!("Failed to create config directory: {}", e))?;
+
+    *writer = Some(Log::open(&app_config_dir.join("debug.log"))?);
+    ENABLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+pub fn log(message: impl AsRef<str>) {
+    // Cheap check first so disabled logging costs nothing on the playback path
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    if let Some(log) = WRITER.lock().unwrap().as_mut() {
+        log.write_line(&format!(
+            "[{}.{:03}] {}",
+            timestamp.as_secs(),
+            timestamp.subsec_millis(),
+            message.as_ref()
+        ));
+    }
+}
+
+// Buffered lines are written out at natural pauses rather than per note,
+// which is also when an oversized log is rotated
+pub fn flush() {
+    let mut writer = WRITER.lock().unwrap();
+    if let Some(log) = writer.as_mut()
+        && let Err(e) = log.flush()
+    {
+        eprintln!("{}; debug logging stopped", e);
+        *writer = None;
+        ENABLED.store(false, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn long_session_rotates_on_flush() {
+        let dir = TempDir::new("debug_log_rotation");
+        let path = dir.path().join("debug.log");
+        let mut log = Log::open(&path).unwrap();
+        let line = "x".repeat(1023);
+        while log.written <= MAX_LOG_BYTES {
+            log.write_line(&line);
+        }
+        log.flush().unwrap();
+
+        let rotated = std::fs::metadata(dir.path().join("debug.log.1")).unwrap();
+        assert!(rotated.len() > MAX_LOG_BYTES);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        log.write_line("after");
+        log.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after\n");
+    }
+}
@@ -5,6 +5,7 @@ use std::io::{Read, Write};
 use std::path::Path;
 
 use crate::Hotkeys;
+use crate::hotkey_utils::HotkeyCombo;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HotkeyConfig {
@@ -12,15 +13,31 @@ pub struct HotkeyConfig {
     pub stop: String,
     pub speed_up: String,
     pub speed_down: String,
+    // Older files predate the panic hotkey; an empty entry falls back to the default
+    #[serde(default)]
+    pub panic: String,
+    #[serde(default)]
+    pub restart: String,
+    #[serde(default)]
+    pub manual_advance: String,
+    #[serde(default)]
+    pub manual_advance_alt: String,
+    #[serde(default)]
+    pub manual_retrigger: String,
 }
 
 impl From<&Hotkeys> for HotkeyConfig {
     fn from(hotkeys: &Hotkeys) -> Self {
         Self {
-            play_pause: format!("{:?}", hotkeys.play_pause),
-            stop: format!("{:?}", hotkeys.stop),
-            speed_up: format!("{:?}", hotkeys.speed_up),
-            speed_down: format!("{:?}", hotkeys.speed_down),
+            play_pause: combo_to_string(hotkeys.play_pause),
+            stop: combo_to_string(hotkeys.stop),
+            speed_up: combo_to_string(hotkeys.speed_up),
+            speed_down: combo_to_string(hotkeys.speed_down),
+            panic: combo_to_string(hotkeys.panic),
+            restart: combo_to_string(hotkeys.restart),
+            manual_advance: combo_to_string(hotkeys.manual_advance),
+            manual_advance_alt: combo_to_string(hotkeys.manual_advance_alt),
+            manual_retrigger: combo_to_string(hotkeys.manual_retrigger),
         }
     }
 }
@@ -49,11 +66,38 @@ pub fn save_hotkeys(hotkeys: &Hotkeys) -> Result<(), String> {
     Ok(())
 }
 
-pub fn load_hotkeys() -> Result<Hotkeys, String> {
+// Forgets the saved bindings so the defaults apply from now on
+pub fn delete_hotkeys() -> Result<(), String> {
     let config_dir =
         dirs::config_dir().ok_or_else(|| "Could not find config directory".to_string())?;
-    let config_path = config_dir.join("sky_sheet_player").join("hotkeys.json");
+    match std::fs::remove_file(config_dir.join("sky_sheet_player").join("hotkeys.json")) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to delete config file: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Always yields usable hotkeys; the second value explains any fallback to defaults
+pub fn load_hotkeys() -> (Hotkeys, Option<String>) {
+    let Some(config_dir) = dirs::config_dir() else {
+        let e = "Could not find config directory".to_string();
+        return (Hotkeys::default(), Some(e));
+    };
+    load_hotkeys_from(&config_dir.join("sky_sheet_player").join("hotkeys.json"))
+}
+
+fn load_hotkeys_from(config_path: &Path) -> (Hotkeys, Option<String>) {
+    match read_hotkeys(config_path) {
+        Ok(hotkeys) => (hotkeys, None),
+        Err(e) => {
+            eprintln!("Hotkey config warning: {}", e);
+            (Hotkeys::default(), Some(e))
+        }
+    }
+}
 
+fn read_hotkeys(config_path: &Path) -> Result<Hotkeys, String> {
     if !config_path.exists() {
         return Ok(Hotkeys::default());
     }
@@ -64,23 +108,84 @@ pub fn load_hotkeys() -> Result<Hotkeys, String> {
     file.read_to_string(&mut contents)
         .map_err(|e| format!("Failed to read config file: {}", e))?;
 
-    let config: HotkeyConfig = serde_json::from_str(&contents)
-        .map_err(|e| format!("Failed to parse config file: {}", e))?;
+    let config: HotkeyConfig = match serde_json::from_str(&contents) {
+        Ok(config) => config,
+        Err(e) => {
+            // Keep the broken file around for inspection instead of failing every startup
+            let backup_path = config_path.with_extension("json.bak");
+            return Err(match std::fs::rename(config_path, &backup_path) {
+                Ok(()) => format!(
+                    "Hotkey config was corrupt ({}), moved to {} and using defaults",
+                    e,
+                    backup_path.display()
+                ),
+                Err(rename_err) => format!(
+                    "Hotkey config was corrupt ({}) and could not be backed up: {}",
+                    e, rename_err
+                ),
+            });
+        }
+    };
 
-    // Convert string keys to Keycode enums
-    let play_pause = parse_keycode(&config.play_pause).unwrap_or(Keycode::Space);
-    let stop = parse_keycode(&config.stop).unwrap_or(Keycode::Escape);
-    let speed_up = parse_keycode(&config.speed_up).unwrap_or(Keycode::Equal);
-    let speed_down = parse_keycode(&config.speed_down).unwrap_or(Keycode::Minus);
+    // Convert strings like "Ctrl+Space" back to combos, keeping defaults for bad entries
+    let defaults = Hotkeys::default();
+    let play_pause = parse_combo(&config.play_pause).unwrap_or(defaults.play_pause);
+    let stop = parse_combo(&config.stop).unwrap_or(defaults.stop);
+    let speed_up = parse_combo(&config.speed_up).unwrap_or(defaults.speed_up);
+    let speed_down = parse_combo(&config.speed_down).unwrap_or(defaults.speed_down);
+    let panic = parse_combo(&config.panic).unwrap_or(defaults.panic);
+    let restart = parse_combo(&config.restart).unwrap_or(defaults.restart);
+    let manual_advance = parse_combo(&config.manual_advance).unwrap_or(defaults.manual_advance);
+    let manual_advance_alt =
+        parse_combo(&config.manual_advance_alt).unwrap_or(defaults.manual_advance_alt);
+    let manual_retrigger =
+        parse_combo(&config.manual_retrigger).unwrap_or(defaults.manual_retrigger);
 
     Ok(Hotkeys {
         play_pause,
         stop,
         speed_up,
         speed_down,
+        panic,
+        restart,
+        manual_advance,
+        manual_advance_alt,
+        manual_retrigger,
     })
 }
 
+// Modifiers first, then the key's variant name, joined with '+'
+fn combo_to_string(combo: HotkeyCombo) -> String {
+    let mut parts = Vec::new();
+    if combo.ctrl {
+        parts.push("Ctrl".to_string());
+    }
+    if combo.shift {
+        parts.push("Shift".to_string());
+    }
+    if combo.alt {
+        parts.push("Alt".to_string());
+    }
+    parts.push(format!("{:?}", combo.key));
+    parts.join("+")
+}
+
+fn parse_combo(combo_str: &str) -> Option<HotkeyCombo> {
+    let mut parts: Vec<&str> = combo_str.split('+').map(str::trim).collect();
+    let key_str = parts.pop()?;
+    let key = parse_keycode(key_str).or_else(|| key_str.parse().ok())?;
+    let mut combo = HotkeyCombo::from(key);
+    for modifier in parts {
+        match modifier {
+            "Ctrl" => combo.ctrl = true,
+            "Shift" => combo.shift = true,
+            "Alt" => combo.alt = true,
+            _ => return None,
+        }
+    }
+    Some(combo)
+}
+
 fn parse_keycode(key_str: &str) -> Option<Keycode> {
     // Manual mapping of keycode strings to Keycode enum variants
     match key_str.trim() {
@@ -88,6 +193,27 @@ fn parse_keycode(key_str: &str) -> Option<Keycode> {
         "Escape" => Some(Keycode::Escape),
         "Equal" => Some(Keycode::Equal),
         "Minus" => Some(Keycode::Minus),
+        "Backspace" => Some(Keycode::Backspace),
+        "Home" => Some(Keycode::Home),
+        "Up" => Some(Keycode::Up),
+        "Down" => Some(Keycode::Down),
+        "Left" => Some(Keycode::Left),
+        "Right" => Some(Keycode::Right),
+        "Enter" => Some(Keycode::Enter),
+        "Insert" => Some(Keycode::Insert),
+        "Delete" => Some(Keycode::Delete),
+        "End" => Some(Keycode::End),
+        "PageUp" => Some(Keycode::PageUp),
+        "PageDown" => Some(Keycode::PageDown),
+        "Semicolon" => Some(Keycode::Semicolon),
+        "Apostrophe" => Some(Keycode::Apostrophe),
+        "Comma" => Some(Keycode::Comma),
+        "Dot" => Some(Keycode::Dot),
+        "Slash" => Some(Keycode::Slash),
+        "BackSlash" => Some(Keycode::BackSlash),
+        "LeftBracket" => Some(Keycode::LeftBracket),
+        "RightBracket" => Some(Keycode::RightBracket),
+        "Grave" => Some(Keycode::Grave),
         "Key1" => Some(Keycode::Key1),
         "Key2" => Some(Keycode::Key2),
         "Key3" => Some(Keycode::Key3),
@@ -127,3 +253,29 @@ fn parse_keycode(key_str: &str) -> Option<Keycode> {
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::fs;
+
+    #[test]
+    fn corrupt_config_falls_back_to_defaults_and_is_backed_up() {
+        let dir = TempDir::new("corrupt_hotkeys");
+        let config_path = dir.path().join("hotkeys.json");
+        fs::write(&config_path, "{ \"play_pause\": garbage").unwrap();
+
+        let (hotkeys, warning) = load_hotkeys_from(&config_path);
+        let defaults = Hotkeys::default();
+        assert_eq!(hotkeys.play_pause, defaults.play_pause);
+        assert_eq!(hotkeys.stop, defaults.stop);
+        assert_eq!(hotkeys.panic, defaults.panic);
+        assert!(warning.unwrap().contains("using defaults"));
+        assert!(!config_path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("hotkeys.json.bak")).unwrap(),
+            "{ \"play_pause\": garbage"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use device_query::Keycode;
+use enigo::Key;
 use std::fmt;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -8,6 +9,11 @@ pub enum HotkeyCapture {
     WaitingForStop,
     WaitingForSpeedUp,
     WaitingForSpeedDown,
+    WaitingForPanic,
+    WaitingForRestart,
+    WaitingForManualAdvance,
+    WaitingForManualAdvanceAlt,
+    WaitingForManualRetrigger,
 }
 
 impl Default for HotkeyCapture {
@@ -43,13 +49,145 @@ pub fn is_valid_hotkey(key: Keycode) -> bool {
     !reserved_keys.contains(&key)
 }
 
-// Human-readable descriptions of keys
-pub fn format_key_description(key: Keycode) -> String {
+// A hotkey along with the modifiers that must be held for it to fire
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HotkeyCombo {
+    pub key: Keycode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl From<Keycode> for HotkeyCombo {
+    fn from(key: Keycode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+}
+
+impl fmt::Display for HotkeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_key_description(*self))
+    }
+}
+
+// Human-readable descriptions of hotkeys, e.g. "Ctrl+Space"
+pub fn format_key_description(combo: HotkeyCombo) -> String {
+    let mut description = String::new();
+    if combo.ctrl {
+        description.push_str("Ctrl+");
+    }
+    if combo.shift {
+        description.push_str("Shift+");
+    }
+    if combo.alt {
+        description.push_str("Alt+");
+    }
+    description.push_str(&key_name(combo.key));
+    description
+}
+
+fn key_name(key: Keycode) -> String {
     match key {
         Keycode::Space => "Space".to_string(),
         Keycode::Escape => "Esc".to_string(),
         Keycode::Equal => "+".to_string(),
         Keycode::Minus => "-".to_string(),
+        Keycode::LeftBracket => "[".to_string(),
         _ => format!("{:?}", key),
     }
 }
+
+// Physical key that types the given character on a US layout
+pub fn char_to_keycode(c: char) -> Option<Keycode> {
+    let keycode = match c.to_ascii_lowercase() {
+        'a' => Keycode::A,
+        'b' => Keycode::B,
+        'c' => Keycode::C,
+        'd' => Keycode::D,
+        'e' => Keycode::E,
+        'f' => Keycode::F,
+        'g' => Keycode::G,
+        'h' => Keycode::H,
+        'i' => Keycode::I,
+        'j' => Keycode::J,
+        'k' => Keycode::K,
+        'l' => Keycode::L,
+        'm' => Keycode::M,
+        'n' => Keycode::N,
+        'o' => Keycode::O,
+        'p' => Keycode::P,
+        'q' => Keycode::Q,
+        'r' => Keycode::R,
+        's' => Keycode::S,
+        't' => Keycode::T,
+        'u' => Keycode::U,
+        'v' => Keycode::V,
+        'w' => Keycode::W,
+        'x' => Keycode::X,
+        'y' => Keycode::Y,
+        'z' => Keycode::Z,
+        '0' => Keycode::Key0,
+        '1' => Keycode::Key1,
+        '2' => Keycode::Key2,
+        '3' => Keycode::Key3,
+        '4' => Keycode::Key4,
+        '5' => Keycode::Key5,
+        '6' => Keycode::Key6,
+        '7' => Keycode::Key7,
+        '8' => Keycode::Key8,
+        '9' => Keycode::Key9,
+        ';' => Keycode::Semicolon,
+        '\'' => Keycode::Apostrophe,
+        ',' => Keycode::Comma,
+        '.' => Keycode::Dot,
+        '/' => Keycode::Slash,
+        '-' => Keycode::Minus,
+        '=' => Keycode::Equal,
+        '[' => Keycode::LeftBracket,
+        ']' => Keycode::RightBracket,
+        '\\' => Keycode::BackSlash,
+        '`' => Keycode::Grave,
+        ' ' => Keycode::Space,
+        _ => return None,
+    };
+    Some(keycode)
+}
+
+// Physical key behind a note output, for the named keys a custom layout can use too
+pub fn key_to_keycode(key: Key) -> Option<Keycode> {
+    Some(match key {
+        Key::Unicode(c) => return char_to_keycode(c),
+        Key::Return => Keycode::Enter,
+        Key::Tab => Keycode::Tab,
+        Key::Space => Keycode::Space,
+        Key::Backspace => Keycode::Backspace,
+        Key::Delete => Keycode::Delete,
+        Key::UpArrow => Keycode::Up,
+        Key::DownArrow => Keycode::Down,
+        Key::LeftArrow => Keycode::Left,
+        Key::RightArrow => Keycode::Right,
+        Key::Home => Keycode::Home,
+        Key::End => Keycode::End,
+        Key::PageUp => Keycode::PageUp,
+        Key::PageDown => Keycode::PageDown,
+        Key::Escape => Keycode::Escape,
+        Key::F1 => Keycode::F1,
+        Key::F2 => Keycode::F2,
+        Key::F3 => Keycode::F3,
+        Key::F4 => Keycode::F4,
+        Key::F5 => Keycode::F5,
+        Key::F6 => Keycode::F6,
+        Key::F7 => Keycode::F7,
+        Key::F8 => Keycode::F8,
+        Key::F9 => Keycode::F9,
+        Key::F10 => Keycode::F10,
+        Key::F11 => Keycode::F11,
+        Key::F12 => Keycode::F12,
+        _ => return None,
+    })
+}
@@ -0,0 +1,343 @@
+use enigo::Key;
+use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
+use std::ops::Deref;
+
+// Number of keys on the Sky instrument grid ("1Key0" through "1Key14")
+pub const GRID_SIZE: usize = 15;
+
+// Key sent for each grid position, indexed by the N in "1KeyN". Usually a character,
+// but a custom layout can use keys that type none, like Enter or the arrows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteKeys(pub [Key; GRID_SIZE]);
+
+impl Default for NoteKeys {
+    fn default() -> Self {
+        KeyLayout::Custom.note_keys()
+    }
+}
+
+impl Deref for NoteKeys {
+    type Target = [Key; GRID_SIZE];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Keys without a character that a custom layout can name, e.g. "Enter" or "Up"
+const NAMED_KEYS: [(&str, Key); 26] = [
+    ("Enter", Key::Return),
+    ("Tab", Key::Tab),
+    ("Space", Key::Space),
+    ("Backspace", Key::Backspace),
+    ("Delete", Key::Delete),
+    ("Up", Key::UpArrow),
+    ("Down", Key::DownArrow),
+    ("Left", Key::LeftArrow),
+    ("Right", Key::RightArrow),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Escape", Key::Escape),
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+];
+
+// Grid positions per octave
+pub const OCTAVE: i32 = 7;
+
+// Key banks a sheet can name: "1Key4", "2Key4" and "3Key4" are the same position
+// in three different banks, e.g. for two-handed or multi-octave arrangements
+pub const BANKS: usize = 3;
+
+// Characters for banks 2 and 3, on QWERTY keys the main grid and default hotkeys
+// leave free. The keyboard runs out before bank 3 does, so its top two are skipped.
+// Other layouts put different characters on those keys, so only QWERTY uses these.
+pub const EXTRA_BANK_KEYS: [&[char]; BANKS - 1] = [
+    &[
+        'q', 'w', 'e', 'r', 't', 'a', 's', 'd', 'f', 'g', 'z', 'x', 'c', 'v', 'b',
+    ],
+    &[
+        '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '`', ']', '\\',
+    ],
+];
+
+// How many positions of a bank have a key; bank 3 stops short of the grid
+pub fn bank_size(bank: usize) -> usize {
+    match bank {
+        0 => GRID_SIZE,
+        bank => EXTRA_BANK_KEYS[bank - 1].len(),
+    }
+}
+
+// Sits between the bank number and the position in a sheet key like "1Key7"
+const KEY_SEPARATOR: &str = "Key";
+
+// Grid position of a sheet key like "1Key7" in any bank, if it names one
+pub fn grid_index(key_str: &str) -> Option<usize> {
+    let index = sheet_position(key_str)?;
+    usize::try_from(index)
+        .ok()
+        .filter(|&index| index < GRID_SIZE)
+}
+
+// Position a sheet key names, even one beyond the grid (e.g. "1Key18" from a wider instrument)
+pub fn sheet_position(key_str: &str) -> Option<i32> {
+    sheet_key(key_str).map(|(_, position)| position)
+}
+
+// Bank (0 for "1Key") and position of a sheet key
+pub fn sheet_key(key_str: &str) -> Option<(usize, i32)> {
+    let (bank, position) = key_str.split_once(KEY_SEPARATOR)?;
+    let bank = bank
+        .parse::<usize>()
+        .ok()
+        .filter(|bank| (1..=BANKS).contains(bank))?;
+    Some((bank - 1, position.parse().ok()?))
+}
+
+// Sheet key for a position in a bank, the inverse of `sheet_key`
+pub fn bank_key(bank: usize, position: i32) -> String {
+    format!("{}{}{}", bank + 1, KEY_SEPARATOR, position)
+}
+
+// Main-bank sheet key for a grid position, the inverse of `grid_index`
+pub fn unmap_key(grid_index: usize) -> Option<String> {
+    (grid_index < GRID_SIZE).then(|| bank_key(0, grid_index as i32))
+}
+
+// Grid position the default layout types this character for, the inverse of `map_key`
+pub fn char_to_grid(c: char) -> Option<usize> {
+    KeyLayout::default()
+        .note_keys()
+        .iter()
+        .position(|&key| key == Key::Unicode(c))
+}
+
+// How a key is shown and saved: the character itself, or its name from NAMED_KEYS
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Unicode(c) => c.to_string(),
+        other => NAMED_KEYS
+            .iter()
+            .find(|&&(_, named)| named == other)
+            .map_or_else(|| format!("{:?}", other), |(name, _)| name.to_string()),
+    }
+}
+
+// The key a name from NAMED_KEYS stands for, ignoring case
+fn named_key(name: &str) -> Option<Key> {
+    NAMED_KEYS
+        .iter()
+        .find(|(named, _)| named.eq_ignore_ascii_case(name))
+        .map(|&(_, key)| key)
+}
+
+// Octave shift that lands the most positions on the grid, preferring the smallest
+// shift on ties, and how many positions stay off it
+pub fn fit_transpose(positions: &[i32]) -> (i32, usize) {
+    let outside = |shift: i32| {
+        positions
+            .iter()
+            .filter(|&&position| !(0..GRID_SIZE as i32).contains(&(position + shift)))
+            .count()
+    };
+    let shift = [0, -OCTAVE, OCTAVE, -2 * OCTAVE, 2 * OCTAVE]
+        .into_iter()
+        .min_by_key(|&shift| outside(shift))
+        .unwrap_or(0);
+    (shift, outside(shift))
+}
+
+// Physical keyboard layouts; each places the grid on the same physical keys
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, Sequence)]
+pub enum KeyLayout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Qwertz,
+    // The player's own 15 keys, kept in the settings file
+    Custom,
+}
+
+impl KeyLayout {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Qwerty => "QWERTY",
+            Self::Azerty => "AZERTY",
+            Self::Qwertz => "QWERTZ",
+            Self::Custom => "Custom",
+        }
+    }
+
+    // Custom starts out as QWERTY; the actual custom keys live in the settings
+    pub fn note_keys(self) -> NoteKeys {
+        let chars = match self {
+            Self::Qwerty | Self::Custom => [
+                'y', 'u', 'i', 'o', 'p', 'h', 'j', 'k', 'l', ';', 'n', 'm', '.', ',', '/',
+            ],
+            Self::Azerty => [
+                'y', 'u', 'i', 'o', 'p', 'h', 'j', 'k', 'l', 'm', 'n', ',', ':', ';', '!',
+            ],
+            Self::Qwertz => [
+                'z', 'u', 'i', 'o', 'p', 'h', 'j', 'k', 'l', 'ö', 'n', 'm', '.', ',', '-',
+            ],
+        };
+        NoteKeys(chars.map(Key::Unicode))
+    }
+
+    // Characters for banks 2 and 3. On AZERTY the digits need Shift and the letters
+    // move, and a custom mapping may already use them, so those play the main bank only.
+    pub fn extra_bank_keys(self) -> [&'static [char]; BANKS - 1] {
+        match self {
+            Self::Qwerty => EXTRA_BANK_KEYS,
+            Self::Azerty | Self::Qwertz | Self::Custom => [&[]; BANKS - 1],
+        }
+    }
+}
+
+// Reads a custom mapping such as "yuiop hjkl; nm.,/", ignoring whitespace. A word
+// matching a key name, like "Enter" or "Up", is that key rather than its letters.
+pub fn parse_note_keys(text: &str) -> Result<NoteKeys, String> {
+    let mut parsed = Vec::new();
+    for word in text.split_whitespace() {
+        match named_key(word) {
+            Some(key) => parsed.push(key),
+            None => parsed.extend(word.chars().map(Key::Unicode)),
+        }
+    }
+    let keys: [Key; GRID_SIZE] = parsed.as_slice().try_into().map_err(|_| {
+        format!(
+            "Custom key mapping needs exactly {} keys, got {}",
+            GRID_SIZE,
+            parsed.len()
+        )
+    })?;
+    if let Some(&duplicate) = keys
+        .iter()
+        .enumerate()
+        .find_map(|(i, key)| keys[..i].contains(key).then_some(key))
+    {
+        return Err(format!(
+            "Custom key mapping uses '{}' twice",
+            key_name(duplicate)
+        ));
+    }
+    Ok(NoteKeys(keys))
+}
+
+// The custom-layout field's text for a mapping; named keys get a word of their own.
+// A run of characters that spells a key name, like "up", is spaced out so it reads
+// back as characters.
+pub fn format_note_keys(keys: &NoteKeys) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut run = String::new();
+    for &key in keys.iter() {
+        match key {
+            Key::Unicode(c) => run.push(c),
+            named => {
+                push_run(&mut words, &mut run);
+                words.push(key_name(named));
+            }
+        }
+    }
+    push_run(&mut words, &mut run);
+    words.join(" ")
+}
+
+fn push_run(words: &mut Vec<String>, run: &mut String) {
+    if run.is_empty() {
+        return;
+    }
+    if named_key(run).is_some() {
+        words.extend(run.chars().map(String::from));
+    } else {
+        words.push(run.clone());
+    }
+    run.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NoteMapping, map_key};
+
+    #[test]
+    fn every_grid_position_round_trips() {
+        let keys = KeyLayout::default().note_keys();
+        let mapping = NoteMapping {
+            keys,
+            transpose: 0,
+            layout: KeyLayout::default(),
+        };
+        for (index, &key) in keys.iter().enumerate() {
+            let sheet = unmap_key(index).unwrap();
+            assert_eq!(grid_index(&sheet), Some(index));
+            assert_eq!(sheet_key(&sheet), Some((0, index as i32)));
+            assert_eq!(map_key(&sheet, &mapping), Some(key));
+            let Key::Unicode(c) = key else {
+                panic!("default layout sends {:?}", key);
+            };
+            assert_eq!(char_to_grid(c), Some(index));
+        }
+        assert_eq!(unmap_key(GRID_SIZE), None);
+    }
+
+    #[test]
+    fn custom_layouts_can_use_named_keys() {
+        let keys = parse_note_keys("yuiop hjkl; nm Enter up F5").unwrap();
+        assert_eq!(keys[..12], KeyLayout::Qwerty.note_keys()[..12]);
+        assert_eq!(keys[12..], [Key::Return, Key::UpArrow, Key::F5]);
+
+        // Saved and shown as names, which read back as the same keys
+        let text = format_note_keys(&keys);
+        assert_eq!(text, "yuiophjkl;nm Enter Up F5");
+        assert_eq!(parse_note_keys(&text), Ok(keys));
+        let names: Vec<String> = keys.iter().map(|&key| key_name(key)).collect();
+        assert_eq!(parse_note_keys(&names.join(" ")), Ok(keys));
+
+        assert_eq!(
+            parse_note_keys("yuiop hjkl; nm. Up Up"),
+            Err("Custom key mapping uses 'Up' twice".to_string())
+        );
+    }
+
+    #[test]
+    fn named_keys_round_trip_before_spelled_names() {
+        let filler = "yiohjkl;nm.,/qwrtasgzxcvb";
+        for spelled in ["up", "End", "F5", "tab"] {
+            for &(name, key) in &NAMED_KEYS {
+                // The characters after the named key spell out another key's name
+                let mut keys: Vec<Key> = filler
+                    .chars()
+                    .filter(|c| !spelled.contains(*c))
+                    .take(GRID_SIZE - 1 - spelled.len())
+                    .map(Key::Unicode)
+                    .collect();
+                keys.push(key);
+                keys.extend(spelled.chars().map(Key::Unicode));
+                let keys = NoteKeys(keys.try_into().unwrap());
+                let text = format_note_keys(&keys);
+                assert_eq!(
+                    parse_note_keys(&text),
+                    Ok(keys),
+                    "{} then {:?}",
+                    name,
+                    spelled
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use enigo::{Direction, Enigo, Key, Keyboard, Settings};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crate::debug_log;
+use crate::key_layout::key_name;
+
+// Where note keys go. Playback only talks to this, so another backend (xdotool,
+// SendInput, ...) only needs these two methods.
+pub trait KeyOutput {
+    fn press(&mut self, key: Key);
+    fn release(&mut self, key: Key);
+}
+
+// Keys an output currently has down, shared with the window so it can show them
+pub type HeldKeys = Arc<Mutex<Vec<Key>>>;
+
+// Passes everything on to another output while keeping `HeldKeys` up to date
+pub struct TrackedOutput {
+    inner: Box<dyn KeyOutput>,
+    held: HeldKeys,
+}
+
+impl TrackedOutput {
+    pub fn new(inner: Box<dyn KeyOutput>, held: HeldKeys) -> Self {
+        Self { inner, held }
+    }
+}
+
+impl KeyOutput for TrackedOutput {
+    fn press(&mut self, key: Key) {
+        self.inner.press(key);
+        let mut held = self.held.lock().unwrap();
+        if !held.contains(&key) {
+            held.push(key);
+        }
+    }
+
+    fn release(&mut self, key: Key) {
+        self.inner.release(key);
+        self.held.lock().unwrap().retain(|&held| held != key);
+    }
+}
+
+// Types into whatever window has focus
+pub struct EnigoOutput {
+    enigo: Enigo,
+}
+
+impl EnigoOutput {
+    pub fn new() -> Result<Self, String> {
+        Enigo::new(&Settings::default())
+            .map(|enigo| Self { enigo })
+            .map_err(|e| format!("Failed to initialize keyboard: {}", e))
+    }
+
+    // Sends one key event, logging failures instead of silently dropping them
+    fn send(&mut self, key: Key, direction: Direction) {
+        if let Err(e) = self.enigo.key(key, direction) {
+            debug_log::log(format!(
+                "Keyboard error on {:?} {:?}: {}",
+                key, direction, e
+            ));
+        }
+    }
+}
+
+impl KeyOutput for EnigoOutput {
+    fn press(&mut self, key: Key) {
+        self.send(key, Direction::Press);
+    }
+
+    fn release(&mut self, key: Key) {
+        self.send(key, Direction::Release);
+    }
+}
+
+// One keyboard connection kept on its own thread for callers that come and go, like
+// manual taps, so each doesn't pay for connecting. Clones share the connection,
+// which closes once the last one is dropped.
+#[derive(Clone)]
+pub struct SharedOutput {
+    events: Sender<(Key, Direction)>,
+}
+
+impl SharedOutput {
+    pub fn spawn() -> Result<Self, String> {
+        let (events, received) = mpsc::channel::<(Key, Direction)>();
+        let (ready, started) = mpsc::channel();
+        thread::spawn(move || {
+            let mut output = match EnigoOutput::new() {
+                Ok(output) => output,
+                Err(e) => {
+                    let _ = ready.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready.send(Ok(()));
+            for (key, direction) in received {
+                output.send(key, direction);
+            }
+        });
+        started
+            .recv()
+            .unwrap_or_else(|_| Err("Failed to initialize keyboard: thread stopped".to_string()))?;
+        Ok(Self { events })
+    }
+}
+
+impl KeyOutput for SharedOutput {
+    fn press(&mut self, key: Key) {
+        let _ = self.events.send((key, Direction::Press));
+    }
+
+    fn release(&mut self, key: Key) {
+        let _ = self.events.send((key, Direction::Release));
+    }
+}
+
+// Hands each character key pressed to a text buffer, e.g. a field in the window;
+// releases and non-character keys have nothing to add. Same locking rule as below.
+pub struct TextOutput {
+    write: Box<dyn FnMut(char) + Send>,
+}
+
+impl TextOutput {
+    pub fn new(write: impl FnMut(char) + Send + 'static) -> Self {
+        Self {
+            write: Box::new(write),
+        }
+    }
+}
+
+impl KeyOutput for TextOutput {
+    fn press(&mut self, key: Key) {
+        if let Key::Unicode(c) = key {
+            (self.write)(c);
+        }
+    }
+
+    fn release(&mut self, _key: Key) {}
+}
+
+// Describes every key event instead of typing it, timed from when it was created.
+// The writer must not lock anything held while keys are pressed or released.
+pub struct LoggingOutput {
+    started: Instant,
+    write: Box<dyn FnMut(String) + Send>,
+}
+
+impl LoggingOutput {
+    pub fn new(write: impl FnMut(String) + Send + 'static) -> Self {
+        Self {
+            started: Instant::now(),
+            write: Box::new(write),
+        }
+    }
+
+    fn log(&mut self, action: &str, key: Key) {
+        let line = format!(
+            "{:>8.3}s  {:<7}  {}",
+            self.started.elapsed().as_secs_f32(),
+            action,
+            key_name(key)
+        );
+        (self.write)(line);
+    }
+}
+
+impl KeyOutput for LoggingOutput {
+    fn press(&mut self, key: Key) {
+        self.log("press", key);
+    }
+
+    fn release(&mut self, key: Key) {
+        self.log("release", key);
+    }
+}
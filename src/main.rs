@@ -1,27 +1,74 @@
+use calibration::LatencyReport;
 use device_query::Keycode;
 use eframe::{App, egui};
-use enigo::{
-    Direction::{Press, Release},
-    Enigo, Key, Keyboard, Settings,
+use enigo::Key;
+use hotkey_utils::{HotkeyCapture, HotkeyCombo, format_key_description, is_valid_hotkey};
+use key_layout::{
+    GRID_SIZE, KeyLayout, NoteKeys, fit_transpose, format_note_keys, key_name, parse_note_keys,
+    sheet_key, sheet_position, unmap_key,
 };
-use hotkey_utils::{HotkeyCapture, format_key_description};
-use rand::Rng;
+use key_output::{
+    EnigoOutput, HeldKeys, KeyOutput, LoggingOutput, SharedOutput, TextOutput, TrackedOutput,
+};
+use player::{LiveSettings, Player, PlayerOptions, Step, playable_speed};
 use rdev::{EventType, Key as RdevKey, listen};
 use rfd::FileDialog;
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::Read;
+use song_file::{
+    LoadedSongs, SHEET_EXTENSIONS, SongError, dedupe_notes, is_song_path, load_song_from_path,
+    load_songs, normalize_folder, write_song_file,
+};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
+use timing::{InstrumentPreset, TimingProfile};
 
+mod app_config;
+mod calibration;
+mod cli;
+mod debug_log;
 mod hotkey_config;
 mod hotkey_utils;
+mod key_layout;
+mod key_output;
+mod metronome;
+mod midi;
+mod player;
+mod rhythm;
+mod sheet_format;
+mod song_file;
+#[cfg(test)]
+mod test_support;
+mod timing;
+mod tray;
+mod window_target;
+
+// How long a toast notification stays on screen
+const TOAST_DURATION: Duration = Duration::from_millis(2500);
+// Slowest playback speed; the fastest is a setting, up to MAX_SPEED_LIMIT
+const MIN_SPEED: f32 = 0.5;
+const MAX_SPEED_LIMIT: f32 = 4.0;
+// One-click speeds under the slider; those above the maximum are hidden
+const SPEED_PRESETS: [f32; 7] = [0.5, 0.75, 1.0, 1.5, 2.0, 3.0, 4.0];
+// How many hotkey changes can be undone
+const HOTKEY_UNDO_LIMIT: usize = 10;
+// A hotkey change is cancelled if no key comes within this long
+const HOTKEY_CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+// Unpinned library entries beyond this many are forgotten
+const RECENT_SONGS_LIMIT: usize = 10;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 struct Note {
     key: String,
     time: u64,
+    // Optional extras some exporters include; older sheets leave them out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    duration: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    velocity: Option<u8>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -43,6 +90,7 @@ struct Song {
 struct AppState {
     song_path: Option<String>,
     speed: f32,
+    max_speed: f32, // Fastest the speed controls go
     is_playing: bool,
     is_paused: bool,
     status: String,
@@ -50,126 +98,464 @@ struct AppState {
     total: usize,
     hotkeys: Hotkeys,
     show_help: bool,
-    hotkey_capture: HotkeyCapture, // Track hotkey capture status
-    manual_mode: bool,             // Manual rhythm mode flag
-    manual_index: usize,           // Current note index for manual mode
-    manual_key_down: bool,         // Track if manual advance key is held
+    hotkey_capture: HotkeyCapture,       // Track hotkey capture status
+    capture_started: Option<Instant>,    // When the capture began, to time it out
+    manual_mode: bool,                   // Manual rhythm mode flag
+    manual_index: usize,                 // Current note index for manual mode
+    manual_key_down: bool,               // Track if manual advance key is held
+    manual_armed: bool,                  // Manual taps play notes; disarming keeps manual_index
+    songs: Arc<Vec<Song>>,               // Parsed songs, shared with playback workers
+    help_text_draft: Option<String>,     // The selected song's notes while they're being edited
+    songs_path: Option<String>,          // File the cached songs were parsed from
+    loading_path: Option<String>,        // File being parsed in the background
+    selected_song: usize,                // Song in the file chosen for playback
+    active_song: usize,                  // Index into `songs` currently playing or shown
+    medley_mode: bool,                   // Play every included song in the file back-to-back
+    medley_included: Vec<bool>,          // Per-song include flags for the medley run
+    medley_gap_secs: f32,                // Silence between medley or playlist songs
+    playlist: Vec<(PathBuf, usize)>,     // Song file and song index, played in order
+    playlist_mode: bool,                 // Play runs the playlist instead of one file
+    humanize_seed: Option<u64>,          // Fixed seed for reproducible hold variation
+    trim_lead_in: bool,                  // Skip the silence before the first note
+    dedupe_notes: bool,                  // Drop repeated key+time notes when loading
+    time_offset_ms: i64,                 // Shift the whole song later, or earlier if negative
+    manual_hold: bool,                   // Hold manual chords until the advance key is released
+    target_window: String,               // Title of the window to focus before playback
+    instrument_preset: InstrumentPreset, // Named bundle of the timing values below
+    timing: TimingProfile,               // Hold/gap/variation used by automatic playback
+    hotkey_history: Vec<Hotkeys>,        // Bindings before each recent change, newest last
+    reset_prompt: Option<SettingsReset>, // Reset waiting for confirmation
+    debug_logging: bool,                 // Write playback events to debug.log
+    toasts: Vec<(String, Instant)>,      // Short-lived confirmations and when they were posted
+    pending_hotkey: Option<(HotkeyCapture, HotkeyCombo)>, // Binding awaiting note-key confirmation
+    key_layout: KeyLayout,               // Keyboard layout the note keys are typed on
+    custom_keys: NoteKeys,               // Note keys for the Custom layout
+    custom_keys_text: String,            // Custom keys as being edited
+    transpose: i32,                      // Grid positions to shift every note by
+    seek_target: Option<usize>,          // Note index the playback worker should jump to
+    scrub_position: usize,               // Note index shown on the seek scrubber
+    scrubbing: bool,                     // Scrubber is being dragged, so don't follow playback
+    loop_enabled: bool,                  // Replay the song until stopped
+    loop_start: Option<usize>,           // A point: first note of the practice loop
+    loop_end: Option<usize>,             // B point: last note before jumping back to A
+    start_note: usize,                   // Automatic playback begins at this note (1-based)
+    auto_play: bool,                     // Start playing as soon as a song is loaded
+    auto_play_pending: bool,             // Auto-play waiting for the old worker to exit
+    minimize_on_play: bool,              // Hide the window when playback starts
+    minimize_pending: bool,              // Playback started; hide on the next frame
+    show_overlay: bool,                  // Compact always-on-top "now playing" window
+    always_on_top: bool,                 // Pin the main window above other windows
+    finish_alert: bool,                  // Chime and flash the taskbar when a song ends
+    attention_pending: bool,             // A song just ended; flash on the next frame
+    playback_active: bool,               // A playback worker thread is running
+    hold_last_chord: bool,               // Let the final chord ring before releasing
+    sustain_chords: bool,                // Hold keys shared by back-to-back chords
+    lead_in_secs: u32,                   // Countdown before the first note
+    expressive_timing: bool,             // Accents, peaks and random variation on holds
+    legato: f32,                         // Share of the time to the next chord a key is held
+    repeat_gap_ms: u64,                  // Least time a key stays up before it's pressed again
+    timing_jitter_ms: u64,               // Most a press may come early or late, 0 for exact timing
+    min_hold_ms: u64,                    // Shortest any key is held, automatic or manual
+    latency: Option<LatencyReport>,      // Last latency measurement
+    measuring_latency: bool,             // A latency measurement is running
+    recording: bool,                     // Record manual taps as a new song
+    record_start: Option<Instant>,       // Time of the first recorded tap
+    recorded_notes: Vec<Note>,           // Taps so far, timed from the first one
+    score_taps: bool,                    // Compare manual taps with the sheet's timing
+    tap_accuracy: rhythm::TapAccuracy,   // Early/late scores for the taps so far
+    preview_mode: bool,                  // Log notes instead of sending keystrokes
+    preview_log: Vec<String>,            // Note events logged in preview mode
+    test_keys: bool,                     // Preview types into the Test Keys field, not the log
+    test_keys_text: String,              // The Test Keys field
+    manual_output: Option<SharedOutput>, // Keyboard connection reused by every manual tap
+    held_keys: HeldKeys,                 // Note keys playback has down right now
+    metronome_enabled: bool,             // Click along at the song's bpm
+    metronome_subdivision: u32,          // Clicks per beat
+    window_size: Option<[f32; 2]>,       // Last known inner size, saved on exit
+    ramp_enabled: bool,                  // Raise the speed a step on every replay
+    ramp_start: f32,                     // Speed the practice ramp begins at
+    ramp_step: f32,                      // Speed added per replay
+    ramp_target: f32,                    // Speed the ramp stops at
+    ramp_pending_start: bool,            // Next playback begins at ramp_start
+    recent_songs: Vec<app_config::RecentSong>, // Library of opened files, newest first
+    batch_rewrite: bool,                 // Folder cleanup saves the sheets back normalized
+    batch_report: Vec<String>,           // Lines from the last folder cleanup
+}
+
+// What a reset button puts back to defaults
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SettingsReset {
+    Hotkeys,
+    // Every saved setting, hotkeys and the library included
+    All,
 }
 
 // Custom struct to hold hotkey settings
 #[derive(Debug, Clone)]
 struct Hotkeys {
-    play_pause: Keycode,
-    stop: Keycode,
-    speed_up: Keycode,
-    speed_down: Keycode,
+    play_pause: HotkeyCombo,
+    stop: HotkeyCombo,
+    speed_up: HotkeyCombo,
+    speed_down: HotkeyCombo,
+    panic: HotkeyCombo, // Releases every note key and stops, regardless of state
+    restart: HotkeyCombo,
+    manual_advance: HotkeyCombo, // Either of these plays the next chord in manual mode
+    manual_advance_alt: HotkeyCombo,
+    manual_retrigger: HotkeyCombo, // Replays the chord just played without advancing
 }
 
 impl Default for Hotkeys {
     fn default() -> Self {
         Self {
-            play_pause: Keycode::Space,
-            stop: Keycode::Escape,
-            speed_up: Keycode::Equal,   // + key
-            speed_down: Keycode::Minus, // - key
+            play_pause: Keycode::Space.into(),
+            stop: Keycode::Escape.into(),
+            speed_up: Keycode::Equal.into(),   // + key
+            speed_down: Keycode::Minus.into(), // - key
+            panic: Keycode::Backspace.into(),
+            restart: Keycode::Home.into(),
+            manual_advance: Keycode::Semicolon.into(),
+            manual_advance_alt: Keycode::Apostrophe.into(),
+            manual_retrigger: Keycode::LeftBracket.into(),
         }
     }
 }
 
 pub struct SkySheetApp {
     state: Arc<Mutex<AppState>>,
+    shutdown: Arc<AtomicBool>, // Tells background threads the app is closing
+    tray: Option<tray::Tray>,  // None if the system has no tray to put an icon in
     last_hotkey_time: std::time::Instant,
 }
 
-impl Default for SkySheetApp {
-    fn default() -> Self {
-        let state = Arc::new(Mutex::new(AppState {
-            speed: 1.0,
-            ..Default::default()
-        }));
-        // Start global hotkey listener thread
-        let state_clone = Arc::clone(&state);
-        std::thread::spawn(move || {
-            if let Err(e) = listen(move |event| {
-                if let EventType::KeyPress(key) = event.event_type {
-                    if let Some(keycode) = rdev_key_to_keycode(key) {
-                        let mut state = state_clone.lock().unwrap();
-                        // Only detect hotkeys if a song is loaded and playback has started at least once
-                        let song_loaded = state.song_path.is_some();
-                        let has_played = state.is_playing || state.progress > 0;
-                        if !song_loaded || !has_played {
-                            return;
-                        }
-                        if state.hotkey_capture == HotkeyCapture::None {
-                            // Manual rhythm mode: listen for ; or '
-                            if state.manual_mode && state.is_playing {
-                                if (keycode == Keycode::Semicolon || keycode == Keycode::Apostrophe)
-                                    && !state.manual_key_down
-                                {
-                                    state.manual_key_down = true;
-                                    let state_arc = Arc::clone(&state_clone);
-                                    std::thread::spawn(move || {
-                                        play_song_manual_tick(state_arc);
-                                    });
-                                    return;
-                                }
-                            }
-                            // Hotkeys
-                            if keycode == state.hotkeys.play_pause {
-                                if state.is_playing {
-                                    state.is_paused = !state.is_paused;
-                                    state.status = if state.is_paused {
-                                        "Paused".to_string()
-                                    } else {
-                                        "Playing...".to_string()
-                                    };
-                                } else if state.song_path.is_some() {
-                                    state.is_playing = true;
-                                    state.status = "Starting playback...".to_string();
-                                    let state_arc = Arc::clone(&state_clone);
-                                    std::thread::spawn(move || {
-                                        play_song_gui(state_arc);
-                                    });
-                                }
-                            } else if keycode == state.hotkeys.stop {
-                                if state.is_playing {
-                                    state.is_playing = false;
-                                    state.is_paused = false;
-                                    state.status = "Stopped".to_string();
-                                }
-                            } else if keycode == state.hotkeys.speed_up {
-                                state.speed += 0.1;
-                                if state.speed > 2.0 {
-                                    state.speed = 2.0;
-                                }
-                                state.status = format!("Speed: {:.1}x", state.speed);
-                            } else if keycode == state.hotkeys.speed_down {
-                                state.speed -= 0.1;
-                                if state.speed < 0.5 {
-                                    state.speed = 0.5;
-                                }
-                                state.status = format!("Speed: {:.1}x", state.speed);
-                            }
+// Default values with the saved settings applied; shared by the window and the CLI
+fn initial_state() -> AppState {
+    let mut state = AppState {
+        speed: 1.0,
+        max_speed: 2.0,
+        medley_gap_secs: 2.0,
+        ramp_start: 0.5,
+        ramp_step: 0.1,
+        ramp_target: 1.0,
+        metronome_subdivision: 1,
+        lead_in_secs: 3,
+        expressive_timing: true,
+        ..Default::default()
+    };
+    let (config, config_warning) = app_config::load_config();
+    let apply_warning = config.apply(&mut state);
+    if let Some(warning) = config_warning.or(apply_warning) {
+        state.status = warning;
+    }
+    state
+}
+
+impl SkySheetApp {
+    fn new(ctx: &egui::Context) -> Self {
+        let mut initial_state = initial_state();
+        // Reopen the last song, unless it has been moved or deleted since
+        let reopen = initial_state.song_path.take().filter(|path| {
+            let exists = std::path::Path::new(path).exists();
+            if !exists {
+                initial_state.status = format!("Last song {} no longer exists", path);
+            }
+            exists
+        });
+        let (hotkeys, hotkey_warning) = hotkey_config::load_hotkeys();
+        initial_state.hotkeys = hotkeys;
+        if let Some(warning) = hotkey_warning {
+            initial_state.status = warning;
+        }
+        if let Err(e) = debug_log::set_enabled(initial_state.debug_logging) {
+            initial_state.status = e;
+        }
+        let state = Arc::new(Mutex::new(initial_state));
+        if let Some(path) = reopen {
+            load_song_in_background(&mut state.lock().unwrap(), &state, &path, false);
+        }
+        let shutdown = Arc::new(AtomicBool::new(false));
+        metronome::spawn(Arc::clone(&state), Arc::clone(&shutdown));
+        spawn_hotkey_listener(Arc::clone(&state), Arc::clone(&shutdown));
+        // Without a tray, hiding falls back to minimizing
+        let tray = tray::create(&state, ctx)
+            .map_err(|e| eprintln!("{}; hiding will minimize instead", e))
+            .ok();
+        Self {
+            state,
+            shutdown,
+            tray,
+            last_hotkey_time: std::time::Instant::now(), // Will be removed below
+        }
+    }
+}
+
+// Compact borderless window above the game with progress and the main controls.
+// Drag anywhere on it to move it.
+fn show_now_playing(ctx: &egui::Context, state: &mut AppState, state_arc: &Arc<Mutex<AppState>>) {
+    ctx.show_viewport_immediate(
+        egui::ViewportId::from_hash_of("now_playing"),
+        egui::ViewportBuilder::default()
+            .with_title("Now playing")
+            .with_inner_size([280.0, 96.0])
+            .with_decorations(false)
+            .with_always_on_top(),
+        |ctx, _class| {
+            if ctx.input(|i| i.viewport().close_requested()) {
+                state.show_overlay = false;
+                save_app_config(state);
+            }
+            egui::CentralPanel::default().show(ctx, |ui| {
+                if ui
+                    .interact(ui.max_rect(), ui.id().with("drag"), egui::Sense::drag())
+                    .drag_started()
+                {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::StartDrag);
+                }
+                let name = state
+                    .songs
+                    .get(state.active_song)
+                    .map_or("No song loaded".to_string(), |song| song.name.clone());
+                ui.horizontal(|ui| {
+                    ui.strong(&name);
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.small_button("✖").clicked() {
+                            state.show_overlay = false;
+                            save_app_config(state);
                         }
-                    }
-                } else if let EventType::KeyRelease(key) = event.event_type {
-                    if let Some(keycode) = rdev_key_to_keycode(key) {
-                        let mut state = state_clone.lock().unwrap();
-                        if state.manual_mode
-                            && (keycode == Keycode::Semicolon || keycode == Keycode::Apostrophe)
+                    });
+                });
+                let (position, total) = if state.manual_mode {
+                    let total = state
+                        .songs
+                        .get(state.active_song)
+                        .map_or(0, |song| song.song_notes.len());
+                    (state.manual_index, total)
+                } else {
+                    (state.progress, state.total)
+                };
+                let fraction = if total > 0 {
+                    position as f32 / total as f32
+                } else {
+                    0.0
+                };
+                ui.add(
+                    egui::ProgressBar::new(fraction).text(format!("note {} / {}", position, total)),
+                );
+                ui.horizontal(|ui| {
+                    let capturing = state.hotkey_capture != HotkeyCapture::None;
+                    if !state.is_playing {
+                        let can_play = state.song_path.is_some() && !state.manual_mode;
+                        if ui
+                            .add_enabled(can_play && !capturing, egui::Button::new("▶"))
+                            .clicked()
+                        {
+                            start_playback(state, state_arc);
+                        }
+                    } else {
+                        let label = if state.is_paused { "▶" } else { "⏸" };
+                        if ui
+                            .add_enabled(!capturing, egui::Button::new(label))
+                            .clicked()
                         {
-                            state.manual_key_down = false;
+                            state.is_paused = !state.is_paused;
+                            state.status = if state.is_paused {
+                                "Paused".to_string()
+                            } else {
+                                "Playing...".to_string()
+                            };
+                        }
+                        if ui.add_enabled(!capturing, egui::Button::new("⏹")).clicked() {
+                            stop_playback(state);
                         }
                     }
+                    ui.separator();
+                    if ui.button("−").clicked() {
+                        state.speed = clamp_speed(state, state.speed - 0.1);
+                    }
+                    ui.label(format!("{:.1}x", state.speed));
+                    if ui.button("+").clicked() {
+                        state.speed = clamp_speed(state, state.speed + 0.1);
+                    }
+                });
+            });
+        },
+    );
+}
+
+// Waits for the next key press to become this hotkey
+fn begin_hotkey_capture(state: &mut AppState, slot: HotkeyCapture, name: &str) {
+    state.hotkey_capture = slot;
+    state.capture_started = Some(Instant::now());
+    state.status = format!("Press any key to set {}...", name);
+}
+
+// Binds the key pressed while a hotkey is waiting to be changed
+fn capture_hotkey(state: &mut AppState, combo: HotkeyCombo) {
+    let slot = std::mem::take(&mut state.hotkey_capture);
+    if let Some(conflict) = hotkey_conflict(state, &slot, combo) {
+        // Refused bindings leave the old hotkey in place
+        state.status = conflict;
+    } else if is_note_output_key(combo.key, &note_mapping(state)) {
+        // The key would both play a note and trigger the hotkey, so ask first
+        state.status = format!(
+            "{} is also a note key; confirm or cancel the binding",
+            format_key_description(combo)
+        );
+        state.pending_hotkey = Some((slot, combo));
+    } else {
+        apply_hotkey(state, slot, combo);
+    }
+}
+
+// Acts on a key press from the global listener. Panic is checked before anything
+// else, even a binding being captured, so it always releases stuck keys; the keys
+// to release are returned for the caller to send.
+fn handle_hotkey_press(
+    state_clone: &Arc<Mutex<AppState>>,
+    pressed: HotkeyCombo,
+) -> Option<NoteMapping> {
+    let mut state = state_clone.lock().unwrap();
+    // Panic release comes first so it works even before a song is loaded, and
+    // abandons a binding being changed rather than becoming the new key
+    if pressed == state.hotkeys.panic {
+        state.hotkey_capture = HotkeyCapture::None;
+        state.is_playing = false;
+        state.is_paused = false;
+        state.manual_armed = false;
+        state.manual_key_down = false;
+        state.status = "Panic: released all keys and stopped".to_string();
+        debug_log::log("Panic release");
+        state.held_keys.lock().unwrap().clear();
+        return Some(note_mapping(&state));
+    }
+    // A binding being changed takes the key here rather than from the
+    // window, so it can be set while the game has focus
+    if state.hotkey_capture != HotkeyCapture::None {
+        capture_hotkey(&mut state, pressed);
+        return None;
+    }
+    // Play/Pause works once a song is loaded; the other hotkeys wait
+    // until playback has started at least once
+    let song_loaded = state.song_path.is_some();
+    if !song_loaded || (!has_played(&state) && pressed != state.hotkeys.play_pause) {
+        return None;
+    }
+    // Manual rhythm mode: listen for the advance keys
+    if state.manual_mode && state.manual_armed {
+        if (pressed == state.hotkeys.manual_advance || pressed == state.hotkeys.manual_advance_alt)
+            && !state.manual_key_down
+        {
+            state.manual_key_down = true;
+            let state_arc = Arc::clone(state_clone);
+            std::thread::spawn(move || {
+                play_song_manual_tick(state_arc, true);
+            });
+            return None;
+        }
+        if pressed == state.hotkeys.manual_retrigger && !state.manual_key_down {
+            state.manual_key_down = true;
+            let state_arc = Arc::clone(state_clone);
+            std::thread::spawn(move || {
+                play_song_manual_tick(state_arc, false);
+            });
+            return None;
+        }
+    }
+    // Hotkeys
+    if pressed == state.hotkeys.play_pause {
+        toggle_play_pause(&mut state, state_clone);
+    } else if pressed == state.hotkeys.restart {
+        restart_song(&mut state);
+    } else if pressed == state.hotkeys.stop {
+        stop_playback(&mut state);
+    } else if pressed == state.hotkeys.speed_up {
+        state.speed = clamp_speed(&state, state.speed + 0.1);
+        state.status = format!("Speed: {:.1}x", state.speed);
+    } else if pressed == state.hotkeys.speed_down {
+        state.speed = clamp_speed(&state, state.speed - 0.1);
+        state.status = format!("Speed: {:.1}x", state.speed);
+    }
+    None
+}
+
+// Global hotkeys come from an rdev hook on its own thread, since they must work
+// while the game has focus
+fn spawn_hotkey_listener(state_clone: Arc<Mutex<AppState>>, shutdown: Arc<AtomicBool>) {
+    std::thread::spawn(move || {
+        // Modifiers held right now, so hotkeys only fire on their full combo
+        let (mut ctrl, mut shift, mut alt) = (false, false, false);
+        if let Err(e) = listen(move |event| {
+            // rdev can't unhook a running listener, so after shutdown events are ignored
+            // until the process exits and the OS drops the hook
+            if shutdown.load(Ordering::Relaxed) {
+                return;
+            }
+            match event.event_type {
+                EventType::KeyPress(RdevKey::ControlLeft | RdevKey::ControlRight) => ctrl = true,
+                EventType::KeyRelease(RdevKey::ControlLeft | RdevKey::ControlRight) => ctrl = false,
+                EventType::KeyPress(RdevKey::ShiftLeft | RdevKey::ShiftRight) => shift = true,
+                EventType::KeyRelease(RdevKey::ShiftLeft | RdevKey::ShiftRight) => shift = false,
+                EventType::KeyPress(RdevKey::Alt | RdevKey::AltGr) => alt = true,
+                EventType::KeyRelease(RdevKey::Alt | RdevKey::AltGr) => alt = false,
+                _ => {}
+            }
+            if let EventType::KeyPress(key) = event.event_type {
+                if let Some(keycode) = rdev_key_to_keycode(key) {
+                    let pressed = HotkeyCombo {
+                        key: keycode,
+                        ctrl,
+                        shift,
+                        alt,
+                    };
+                    if let Some(mapping) = handle_hotkey_press(&state_clone, pressed) {
+                        // Connecting to the keyboard takes a moment, so not on the hook thread
+                        std::thread::spawn(move || match EnigoOutput::new() {
+                            Ok(mut output) => release_all_keys(&mut output, &mapping),
+                            Err(e) => eprintln!("{} for panic release", e),
+                        });
+                    }
+                }
+            } else if let EventType::KeyRelease(key) = event.event_type {
+                if let Some(keycode) = rdev_key_to_keycode(key) {
+                    let mut state = state_clone.lock().unwrap();
+                    // Modifiers may already be up, so only the key itself has to match
+                    if state.manual_mode
+                        && (keycode == state.hotkeys.manual_advance.key
+                            || keycode == state.hotkeys.manual_advance_alt.key
+                            || keycode == state.hotkeys.manual_retrigger.key)
+                    {
+                        state.manual_key_down = false;
+                    }
                 }
-            }) {
-                eprintln!("Global hotkey listener error: {:?}", e);
             }
-        });
-        Self {
-            state,
-            last_hotkey_time: std::time::Instant::now(), // Will be removed below
+        }) {
+            eprintln!("Global hotkey listener error: {:?}", e);
         }
+    });
+}
+
+// What the Play/Pause hotkey does: arms or disarms manual mode, pauses or resumes
+// running playback, or starts it
+fn toggle_play_pause(state: &mut AppState, state_arc: &Arc<Mutex<AppState>>) {
+    if state.manual_mode {
+        let armed = !state.manual_armed;
+        set_manual_armed(state, armed);
+    } else if state.is_playing {
+        state.is_paused = !state.is_paused;
+        state.status = if state.is_paused {
+            "Paused".to_string()
+        } else {
+            "Playing...".to_string()
+        };
+    } else if state.song_path.is_some() {
+        start_playback(state, state_arc);
+    }
+}
+
+fn stop_playback(state: &mut AppState) {
+    if state.is_playing {
+        state.is_playing = false;
+        state.is_paused = false;
+        state.status = "Stopped".to_string();
     }
 }
 
@@ -190,60 +576,88 @@ impl App for SkySheetApp {
         // Only keep hotkey capture logic (for changing hotkeys) and UI
         let state_clone = Arc::clone(&self.state);
         let mut state = state_clone.lock().unwrap();
-        // Hotkey capture (for changing hotkeys) still works when focused
-        if state.hotkey_capture != HotkeyCapture::None {
-            if let Some(key) = ctx.input(|i| {
-                i.events.iter().find_map(move |e| match e {
-                    egui::Event::Key {
-                        key, pressed: true, ..
-                    } => Some(*key),
-                    _ => None,
-                })
-            }) {
-                use egui::Key;
-                let keycode = match key {
-                    Key::Space => Keycode::Space,
-                    Key::Escape => Keycode::Escape,
-                    Key::Equals => Keycode::Equal,
-                    Key::Minus => Keycode::Minus,
-                    Key::Semicolon => Keycode::Semicolon,
-                    Key::Quote => Keycode::Apostrophe,
-                    // Add more as needed
-                    _ => return,
-                };
-                match state.hotkey_capture {
-                    HotkeyCapture::WaitingForPlayPause => {
-                        state.hotkeys.play_pause = keycode;
-                        state.status = format!(
-                            "Play/Pause hotkey set to: {}",
-                            format_key_description(keycode)
-                        );
-                    }
-                    HotkeyCapture::WaitingForStop => {
-                        state.hotkeys.stop = keycode;
-                        state.status =
-                            format!("Stop hotkey set to: {}", format_key_description(keycode));
-                    }
-                    HotkeyCapture::WaitingForSpeedUp => {
-                        state.hotkeys.speed_up = keycode;
-                        state.status = format!(
-                            "Speed Up hotkey set to: {}",
-                            format_key_description(keycode)
-                        );
-                    }
-                    HotkeyCapture::WaitingForSpeedDown => {
-                        state.hotkeys.speed_down = keycode;
-                        state.status = format!(
-                            "Speed Down hotkey set to: {}",
-                            format_key_description(keycode)
-                        );
-                    }
-                    _ => {}
-                }
-                state.hotkey_capture = HotkeyCapture::None;
+        if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+            state.window_size = Some([rect.width(), rect.height()]);
+        }
+        // Give up on a capture nobody answered, so the controls don't stay locked
+        if state.hotkey_capture != HotkeyCapture::None
+            && state
+                .capture_started
+                .is_some_and(|started| started.elapsed() >= HOTKEY_CAPTURE_TIMEOUT)
+        {
+            state.hotkey_capture = HotkeyCapture::None;
+            state.status = "Hotkey change timed out; the old binding is kept".to_string();
+        }
+
+        if state.minimize_pending {
+            state.minimize_pending = false;
+            hide_window(ctx, self.tray.is_some());
+        }
+        if state.attention_pending {
+            state.attention_pending = false;
+            ctx.send_viewport_cmd(egui::ViewportCommand::RequestUserAttention(
+                egui::UserAttentionType::Informational,
+            ));
+        }
+        if state.show_overlay {
+            show_now_playing(ctx, &mut state, &self.state);
+        }
+
+        // Auto-play waits for any previous worker to wind down so two never run at once
+        if state.auto_play_pending && !state.playback_active {
+            state.auto_play_pending = false;
+            if !state.is_playing && !state.manual_mode {
+                start_playback(&mut state, &self.state);
+            }
+        }
+
+        // Enter acts as the default Play action; when a control has keyboard focus
+        // (reached with Tab) egui gives Enter to that control instead
+        let enter_pressed = ctx.input(|i| i.key_pressed(egui::Key::Enter));
+        let nothing_focused = ctx.memory(|m| m.focused().is_none());
+        if enter_pressed
+            && nothing_focused
+            && state.hotkey_capture == HotkeyCapture::None
+            && state.song_path.is_some()
+            && !state.is_playing
+            && !state.manual_mode
+        {
+            start_playback(&mut state, &self.state);
+        }
+
+        // A file dropped on the window opens just like one picked in the dialog
+        let dropped = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .find_map(|file| file.path.clone())
+        });
+        if let Some(path) = dropped {
+            let path = path.display().to_string();
+            if is_song_path(&path) {
+                open_song_file(&mut state, &self.state, &path);
+            } else {
+                state.status = format!("{} is not a song sheet or MIDI file", path);
             }
         }
 
+        // Toasts expire on their own and stack in the bottom-right corner
+        state
+            .toasts
+            .retain(|(_, posted)| posted.elapsed() < TOAST_DURATION);
+        if !state.toasts.is_empty() {
+            egui::Area::new(egui::Id::new("toasts"))
+                .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    for (message, _) in &state.toasts {
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.label(message);
+                        });
+                    }
+                });
+        }
+
         // Draw the UI with an improved layout and theme
         egui::TopBottomPanel::top("header").show(ctx, |ui| {
             ui.add_space(8.0);
@@ -265,6 +679,137 @@ impl App for SkySheetApp {
             ui.add_space(8.0);
         });
 
+        egui::SidePanel::left("library")
+            .resizable(true)
+            .default_width(180.0)
+            .show(ctx, |ui| {
+                ui.heading("Library");
+                if state.recent_songs.is_empty() {
+                    ui.label("Songs you open will show up here.");
+                }
+                let mut open = None;
+                let mut toggle_pin = None;
+                let mut remove = None;
+                // Pinned songs stay on top; the rest keep most-recent-first order
+                let mut order: Vec<usize> = (0..state.recent_songs.len()).collect();
+                order.sort_by_key(|&i| !state.recent_songs[i].pinned);
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for i in order {
+                        let entry = &state.recent_songs[i];
+                        let exists = std::path::Path::new(&entry.path).exists();
+                        ui.horizontal(|ui| {
+                            if ui
+                                .small_button(if entry.pinned { "★" } else { "☆" })
+                                .on_hover_text("Pin to the top")
+                                .clicked()
+                            {
+                                toggle_pin = Some(i);
+                            }
+                            if exists {
+                                if ui
+                                    .selectable_label(
+                                        state.song_path.as_deref() == Some(entry.path.as_str()),
+                                        &entry.name,
+                                    )
+                                    .on_hover_text(&entry.path)
+                                    .clicked()
+                                {
+                                    open = Some(i);
+                                }
+                            } else {
+                                ui.weak(&entry.name)
+                                    .on_hover_text(format!("Missing: {}", entry.path));
+                                if ui
+                                    .small_button("✖")
+                                    .on_hover_text("Remove from library")
+                                    .clicked()
+                                {
+                                    remove = Some(i);
+                                }
+                            }
+                        });
+                    }
+                });
+                if let Some(i) = toggle_pin {
+                    state.recent_songs[i].pinned = !state.recent_songs[i].pinned;
+                    save_app_config(&mut state);
+                }
+                if let Some(i) = remove {
+                    state.recent_songs.remove(i);
+                    save_app_config(&mut state);
+                }
+                if let Some(i) = open {
+                    let path = state.recent_songs[i].path.clone();
+                    open_song_file(&mut state, &self.state, &path);
+                }
+
+                ui.separator();
+                ui.heading("Playlist");
+                let mut playlist_changed = ui
+                    .checkbox(&mut state.playlist_mode, "Play the playlist")
+                    .on_hover_text("Play runs every entry in order; loop repeats the whole list")
+                    .changed();
+                let current = state.song_path.clone().filter(|_| !state.songs.is_empty());
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(current.is_some(), egui::Button::new("➕ Add song"))
+                        .on_hover_text("Add the selected song to the end")
+                        .clicked()
+                        && let Some(path) = current
+                    {
+                        let entry = (PathBuf::from(path), state.selected_song);
+                        state.playlist.push(entry);
+                        playlist_changed = true;
+                    }
+                    if ui
+                        .add_enabled(!state.playlist.is_empty(), egui::Button::new("Clear"))
+                        .clicked()
+                    {
+                        state.playlist.clear();
+                        playlist_changed = true;
+                    }
+                });
+                let mut swap = None;
+                let mut remove = None;
+                let last = state.playlist.len().saturating_sub(1);
+                for (i, (path, song_index)) in state.playlist.iter().enumerate() {
+                    let name = path.file_stem().map_or_else(
+                        || path.display().to_string(),
+                        |stem| stem.to_string_lossy().into_owned(),
+                    );
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(i > 0, egui::Button::new("⬆").small())
+                            .clicked()
+                        {
+                            swap = Some((i - 1, i));
+                        }
+                        if ui
+                            .add_enabled(i < last, egui::Button::new("⬇").small())
+                            .clicked()
+                        {
+                            swap = Some((i, i + 1));
+                        }
+                        if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                            remove = Some(i);
+                        }
+                        ui.label(format!("{}. {} #{}", i + 1, name, song_index + 1))
+                            .on_hover_text(path.display().to_string());
+                    });
+                }
+                if let Some((a, b)) = swap {
+                    state.playlist.swap(a, b);
+                    playlist_changed = true;
+                }
+                if let Some(i) = remove {
+                    state.playlist.remove(i);
+                    playlist_changed = true;
+                }
+                if playlist_changed {
+                    save_app_config(&mut state);
+                }
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             if state.show_help {
                 // Help section
@@ -276,8 +821,11 @@ impl App for SkySheetApp {
                         ui.label("Play/Pause:");
                         ui.label(format_key_description(state.hotkeys.play_pause));
                         if ui.button("Change").clicked() {
-                            state.hotkey_capture = HotkeyCapture::WaitingForPlayPause;
-                            state.status = "Press any key to set Play/Pause hotkey...".to_string();
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForPlayPause,
+                                "Play/Pause hotkey",
+                            );
                         }
                     });
 
@@ -285,8 +833,11 @@ impl App for SkySheetApp {
                         ui.label("Stop:");
                         ui.label(format_key_description(state.hotkeys.stop));
                         if ui.button("Change").clicked() {
-                            state.hotkey_capture = HotkeyCapture::WaitingForStop;
-                            state.status = "Press any key to set Stop hotkey...".to_string();
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForStop,
+                                "Stop hotkey",
+                            );
                         }
                     });
 
@@ -294,8 +845,11 @@ impl App for SkySheetApp {
                         ui.label("Speed Up:");
                         ui.label(format_key_description(state.hotkeys.speed_up));
                         if ui.button("Change").clicked() {
-                            state.hotkey_capture = HotkeyCapture::WaitingForSpeedUp;
-                            state.status = "Press any key to set Speed Up hotkey...".to_string();
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForSpeedUp,
+                                "Speed Up hotkey",
+                            );
                         }
                     });
 
@@ -303,19 +857,169 @@ impl App for SkySheetApp {
                         ui.label("Speed Down:");
                         ui.label(format_key_description(state.hotkeys.speed_down));
                         if ui.button("Change").clicked() {
-                            state.hotkey_capture = HotkeyCapture::WaitingForSpeedDown;
-                            state.status = "Press any key to set Speed Down hotkey...".to_string();
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForSpeedDown,
+                                "Speed Down hotkey",
+                            );
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Panic (release all keys):");
+                        ui.label(format_key_description(state.hotkeys.panic));
+                        if ui.button("Change").clicked() {
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForPanic,
+                                "Panic hotkey",
+                            );
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Restart:");
+                        ui.label(format_key_description(state.hotkeys.restart));
+                        if ui.button("Change").clicked() {
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForRestart,
+                                "Restart hotkey",
+                            );
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Manual advance:");
+                        ui.label(format_key_description(state.hotkeys.manual_advance));
+                        if ui.button("Change").clicked() {
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForManualAdvance,
+                                "the manual advance key",
+                            );
+                        }
+                        ui.label(format_key_description(state.hotkeys.manual_advance_alt));
+                        if ui.button("Change").clicked() {
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForManualAdvanceAlt,
+                                "the second manual advance key",
+                            );
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Manual replay:");
+                        ui.label(format_key_description(state.hotkeys.manual_retrigger));
+                        if ui.button("Change").clicked() {
+                            begin_hotkey_capture(
+                                &mut state,
+                                HotkeyCapture::WaitingForManualRetrigger,
+                                "the manual replay key",
+                            );
+                        }
+                    });
+
+                    if let Some((slot, combo)) = state.pending_hotkey.clone() {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(220, 200, 110),
+                                format!(
+                                    "⚠ {} is also a note key and will play notes too.",
+                                    format_key_description(combo)
+                                ),
+                            );
+                            if ui.button("Bind anyway").clicked() {
+                                state.pending_hotkey = None;
+                                apply_hotkey(&mut state, slot, combo);
+                            }
+                            if ui.button("Cancel").clicked() {
+                                state.pending_hotkey = None;
+                                state.status = "Hotkey change cancelled".to_string();
+                            }
+                        });
+                    }
+
+                    let undo_label = match state.hotkey_history.len() {
+                        0 => "Undo last change".to_string(),
+                        steps => format!("Undo last change ({} left)", steps),
+                    };
+                    if ui
+                        .add_enabled(
+                            !state.hotkey_history.is_empty(),
+                            egui::Button::new(undo_label),
+                        )
+                        .clicked()
+                        && let Some(previous) = state.hotkey_history.pop()
+                    {
+                        state.hotkeys = previous;
+                        state.status = "Restored previous hotkeys".to_string();
+                        save_hotkey_config(&mut state);
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Reset hotkeys to defaults").clicked() {
+                            state.reset_prompt = Some(SettingsReset::Hotkeys);
+                        }
+                        if ui.button("Reset all settings").clicked() {
+                            state.reset_prompt = Some(SettingsReset::All);
                         }
                     });
+                    if let Some(reset) = state.reset_prompt {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(
+                                egui::Color32::from_rgb(220, 200, 110),
+                                match reset {
+                                    SettingsReset::Hotkeys => {
+                                        "⚠ Put every hotkey back to its default?"
+                                    }
+                                    SettingsReset::All => {
+                                        "⚠ Delete all saved settings, hotkeys and the library?"
+                                    }
+                                },
+                            );
+                            if ui.button("Reset").clicked() {
+                                state.reset_prompt = None;
+                                reset_settings(&mut state, reset);
+                                ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                                    window_level(state.always_on_top),
+                                ));
+                            }
+                            if ui.button("Cancel").clicked() {
+                                state.reset_prompt = None;
+                            }
+                        });
+                    }
 
                     ui.add_space(10.0);
                     ui.heading("How to Use");
                     ui.label(
-                        "1. Click 'Select Song File' and choose a .txt file with JSON song data",
+                        "1. Click 'Select Song File' and choose a .txt/.json sheet, \
+                         or drop one on the window",
                     );
                     ui.label("2. Adjust speed with the slider or hotkeys if needed");
                     ui.label("3. Click 'Play' or press the play hotkey");
                     ui.label("4. Use the pause/stop buttons or hotkeys to control playback");
+                    ui.label(
+                        "Keyboard: Tab moves between controls and Enter activates them; \
+                         Enter with nothing focused starts playback",
+                    );
+
+                    ui.add_space(10.0);
+                    if ui
+                        .checkbox(
+                            &mut state.debug_logging,
+                            "Debug logging (debug.log in the settings folder, \
+                             attach it to bug reports)",
+                        )
+                        .changed()
+                    {
+                        if let Err(e) = debug_log::set_enabled(state.debug_logging) {
+                            state.debug_logging = false;
+                            state.status = e;
+                        }
+                        save_app_config(&mut state);
+                    }
                 });
                 ui.add_space(10.0);
             }
@@ -324,21 +1028,31 @@ impl App for SkySheetApp {
                 // File selection row
                 ui.horizontal(|ui| {
                     if ui.button("📂 Select Song File").clicked() {
-                        if let Some(path) =
-                            FileDialog::new().add_filter("Text", &["txt"]).pick_file()
+                        if let Some(path) = FileDialog::new()
+                            .add_filter("Song sheets", SHEET_EXTENSIONS)
+                            .pick_file()
                         {
-                            state.song_path = Some(path.display().to_string());
-                            state.status = "Song loaded!".to_string();
-                            state.manual_index = 0; // Reset manual index on new song
-                            if state.manual_mode {
-                                state.is_playing = true; // Ensure manual mode is ready after new song
-                            } else {
-                                state.is_playing = false;
-                            }
-                            state.progress = 0;
+                            open_song_file(&mut state, &self.state, &path.display().to_string());
                         }
                     }
-                    if let Some(ref path) = state.song_path {
+                    if ui.button("🎹 Import MIDI").clicked()
+                        && let Some(path) = FileDialog::new()
+                            .add_filter("MIDI", &["mid", "midi"])
+                            .pick_file()
+                    {
+                        let path = path.display().to_string();
+                        load_song_in_background(&mut state, &self.state, &path, false);
+                    }
+                    if ui
+                        .add_enabled(!state.songs.is_empty(), egui::Button::new("💾 Export MIDI"))
+                        .clicked()
+                    {
+                        export_midi(&mut state);
+                    }
+                    if let Some(ref path) = state.loading_path {
+                        ui.spinner();
+                        ui.label(format!("Loading: {}", path));
+                    } else if let Some(ref path) = state.song_path {
                         ui.label(format!("Selected: {}", path));
                     } else {
                         ui.label("No file selected");
@@ -358,49 +1072,324 @@ impl App for SkySheetApp {
                         .clicked()
                     {
                         state.manual_mode = !state.manual_mode;
+                        save_app_config(&mut state);
                         if state.manual_mode {
-                            state.status =
-                                "Manual rhythm mode enabled! Press ; or ' to advance.".to_string();
+                            state.status = format!(
+                                "Manual rhythm mode enabled! Press {} or {} to advance, \
+                                 {} to replay.",
+                                format_key_description(state.hotkeys.manual_advance),
+                                format_key_description(state.hotkeys.manual_advance_alt),
+                                format_key_description(state.hotkeys.manual_retrigger)
+                            );
                             state.manual_index = 0;
-                            if state.song_path.is_some() {
-                                state.is_playing = true; // Enable manual tick handler
-                            }
+                            state.tap_accuracy.reset();
+                            // Automatic playback and manual taps never run together
+                            state.is_playing = false;
+                            state.is_paused = false;
+                            state.manual_armed = state.song_path.is_some();
                         } else {
                             state.status = "Manual rhythm mode disabled.".to_string();
-                            state.is_playing = false; // Disable manual tick handler
+                            state.manual_armed = false;
+                            if state.recording {
+                                state.recording = false;
+                                save_recording(&mut state);
+                            }
+                        }
+                    }
+                    if state.manual_mode {
+                        let label = if state.manual_armed {
+                            "⏸ Disarm"
+                        } else {
+                            "▶ Arm"
+                        };
+                        if ui.button(label).clicked() {
+                            let armed = !state.manual_armed;
+                            set_manual_armed(&mut state, armed);
                         }
                     }
                 });
-            });
-
-            ui.add_space(10.0);
-
-            // Playback controls
-            ui.group(|ui| {
-                ui.vertical(|ui| {
-                    // Main playback controls in a row
-                    ui.horizontal(|ui| {
-                        ui.add_space(10.0);
-
-                        let btn_size = egui::Vec2::new(60.0, 40.0);
-
-                        if !state.is_playing {
-                            // Disable Play button if manual mode is enabled
-                            let play_btn = egui::Button::new("▶️ Play")
-                                .min_size(btn_size)
-                                .fill(egui::Color32::from_rgb(50, 180, 100));
-                            if ui.add_enabled(!state.manual_mode, play_btn).clicked() {
-                                let state_arc = Arc::clone(&self.state);
-                                state.is_playing = true;
-                                state.status = "Starting playback...".to_string();
-                                std::thread::spawn(move || {
-                                    play_song_gui(state_arc);
-                                });
+                ui.horizontal(|ui| {
+                    let mut changed = false;
+                    egui::ComboBox::from_label("Keyboard layout")
+                        .selected_text(state.key_layout.name())
+                        .show_ui(ui, |ui| {
+                            for layout in enum_iterator::all::<KeyLayout>() {
+                                changed |= ui
+                                    .selectable_value(&mut state.key_layout, layout, layout.name())
+                                    .changed();
                             }
-                        } else {
-                            if state.is_paused {
-                                if ui
-                                    .add(
+                        });
+                    if changed {
+                        save_app_config(&mut state);
+                    }
+                });
+                if state.key_layout == KeyLayout::Custom {
+                    ui.horizontal(|ui| {
+                        ui.label("Custom keys:");
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut state.custom_keys_text)
+                                .hint_text("15 keys, top row first, or names like Enter"),
+                        );
+                        // Only a complete, valid mapping replaces the current one
+                        if response.lost_focus() {
+                            match parse_note_keys(&state.custom_keys_text) {
+                                Ok(keys) => {
+                                    state.custom_keys = keys;
+                                    save_app_config(&mut state);
+                                }
+                                Err(e) => {
+                                    state.status = e;
+                                    state.custom_keys_text = format_note_keys(&state.custom_keys);
+                                }
+                            }
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Transpose:");
+                    if ui.small_button("−").clicked() {
+                        state.transpose -= 1;
+                    }
+                    ui.add(egui::DragValue::new(&mut state.transpose).range(-14..=14));
+                    if ui.small_button("+").clicked() {
+                        state.transpose += 1;
+                    }
+                    state.transpose = state.transpose.clamp(-14, 14);
+                    if ui
+                        .button("Fit to range")
+                        .on_hover_text("Shift by octaves so the most notes land on the 15 keys")
+                        .clicked()
+                    {
+                        fit_song_to_range(&mut state);
+                    }
+                    if let Some(song) = state.songs.get(state.active_song) {
+                        ui.label(format!("(sheet pitch level {})", song.pitch_level));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut state.trim_lead_in, "Auto-trim lead-in")
+                        .changed()
+                    {
+                        save_app_config(&mut state);
+                    }
+                    if let Some(song) = state.songs.get(state.active_song) {
+                        ui.label(format!(
+                            "(detected {:.1} s)",
+                            lead_in_ms(song) as f32 / 1000.0
+                        ));
+                    }
+                    ui.label("Offset:");
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut state.time_offset_ms)
+                                .range(-10_000..=10_000)
+                                .speed(10)
+                                .suffix(" ms"),
+                        )
+                        .on_hover_text(
+                            "Moves the whole song later, or earlier when negative, \
+                             e.g. to line up with an in-game cue",
+                        )
+                        .changed()
+                    {
+                        save_app_config(&mut state);
+                    }
+                    if ui
+                        .checkbox(&mut state.dedupe_notes, "Remove duplicate notes")
+                        .on_hover_text(
+                            "Skip notes that repeat the same key at the same time; \
+                             applies from the next load",
+                        )
+                        .changed()
+                    {
+                        save_app_config(&mut state);
+                    }
+                });
+                if state.manual_mode {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .checkbox(&mut state.manual_hold, "Hold notes while key is held")
+                            .changed()
+                        {
+                            save_app_config(&mut state);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui.checkbox(&mut state.recording, "⏺ Record taps").changed() {
+                            if state.recording {
+                                state.recorded_notes.clear();
+                                state.record_start = None;
+                                state.status =
+                                    "Recording: your taps will be saved as a new song".to_string();
+                            } else {
+                                save_recording(&mut state);
+                            }
+                        }
+                        if !state.recorded_notes.is_empty() {
+                            ui.label(format!("{} notes recorded", state.recorded_notes.len()));
+                            if !state.recording && ui.button("💾 Save recording").clicked() {
+                                save_recording(&mut state);
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        if ui
+                            .checkbox(&mut state.score_taps, "🎯 Score my timing")
+                            .changed()
+                        {
+                            state.tap_accuracy.reset();
+                        }
+                        if state.score_taps {
+                            if let Some(last) = state.tap_accuracy.last() {
+                                let (early, on_time, late) = state.tap_accuracy.counts();
+                                ui.label(format!(
+                                    "Last: {}  ·  early {}  on time {}  late {}",
+                                    rhythm::describe(last),
+                                    early,
+                                    on_time,
+                                    late
+                                ));
+                                if let Some(offset) = state.tap_accuracy.mean_offset_ms() {
+                                    ui.label(format!("(avg {} ms off)", offset));
+                                }
+                            } else {
+                                ui.label("Tap along; each tap is compared with the sheet");
+                            }
+                            if ui.small_button("Reset").clicked() {
+                                state.tap_accuracy.reset();
+                            }
+                        }
+                    });
+                }
+                // Song selector and medley controls only make sense for files
+                // holding several songs
+                if state.songs.len() > 1 {
+                    let songs = Arc::clone(&state.songs);
+                    let mut selected = state.selected_song;
+                    egui::ComboBox::from_label("Song")
+                        .selected_text(
+                            songs
+                                .get(selected)
+                                .map_or("", |song| song.name.as_str())
+                                .to_string(),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (index, song) in songs.iter().enumerate() {
+                                ui.selectable_value(
+                                    &mut selected,
+                                    index,
+                                    format!("{}. {}", index + 1, song.name),
+                                );
+                            }
+                        });
+                    if selected != state.selected_song {
+                        state.selected_song = selected;
+                        state.transpose = songs[selected].pitch_level;
+                        if !state.is_playing || state.manual_mode {
+                            state.active_song = selected;
+                        }
+                        state.manual_index = 0;
+                        state.progress = 0;
+                        state.total = songs[selected].song_notes.len();
+                        state.seek_target = None;
+                        state.loop_start = None;
+                        state.loop_end = None;
+                        state.start_note = 1;
+                        state.tap_accuracy.reset();
+                        state.help_text_draft = None;
+                        state.status = format!("Selected {}", song_summary(&songs[selected]));
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut state.medley_mode, "Play all songs in file");
+                        ui.label("Gap:");
+                        ui.add(
+                            egui::DragValue::new(&mut state.medley_gap_secs)
+                                .range(0.0..=30.0)
+                                .speed(0.1)
+                                .suffix(" s"),
+                        );
+                    });
+                    if state.medley_mode {
+                        let state = &mut *state;
+                        for (song, included) in
+                            state.songs.iter().zip(state.medley_included.iter_mut())
+                        {
+                            ui.checkbox(included, &song.name);
+                        }
+                    }
+                }
+                // The sheet's own notes, often performance tips or credits
+                if let Some(song) = state.songs.get(state.selected_song) {
+                    let help_text = song.help_text.clone();
+                    egui::CollapsingHeader::new("Sheet notes")
+                        .default_open(!help_text.trim().is_empty())
+                        .show(ui, |ui| match state.help_text_draft.as_mut() {
+                            Some(draft) => {
+                                ui.add(
+                                    egui::TextEdit::multiline(draft)
+                                        .desired_rows(3)
+                                        .desired_width(f32::INFINITY),
+                                );
+                                ui.horizontal(|ui| {
+                                    let can_save =
+                                        state.song_path.as_deref().is_some_and(is_song_path);
+                                    if ui
+                                        .add_enabled(can_save, egui::Button::new("💾 Save to file"))
+                                        .on_disabled_hover_text("Only sheet files can be saved")
+                                        .clicked()
+                                    {
+                                        save_help_text(&mut state);
+                                    }
+                                    if ui.button("Cancel").clicked() {
+                                        state.help_text_draft = None;
+                                    }
+                                });
+                            }
+                            None => {
+                                if help_text.trim().is_empty() {
+                                    ui.weak("This sheet has no notes");
+                                } else {
+                                    ui.label(&help_text);
+                                }
+                                if ui.small_button("✏ Edit").clicked() {
+                                    state.help_text_draft = Some(help_text);
+                                }
+                            }
+                        });
+                }
+            });
+
+            ui.add_space(10.0);
+
+            // Playback controls
+            ui.group(|ui| {
+                ui.vertical(|ui| {
+                    // Main playback controls in a row
+                    ui.horizontal(|ui| {
+                        ui.add_space(10.0);
+
+                        let btn_size = egui::Vec2::new(60.0, 40.0);
+                        // A click mid-capture would leave the binding half-changed
+                        let capturing = state.hotkey_capture != HotkeyCapture::None;
+
+                        if !state.is_playing {
+                            // Disable Play button if manual mode is enabled
+                            let play_btn = egui::Button::new("▶️ Play")
+                                .min_size(btn_size)
+                                .fill(egui::Color32::from_rgb(50, 180, 100));
+                            if ui
+                                .add_enabled(!state.manual_mode && !capturing, play_btn)
+                                .clicked()
+                            {
+                                start_playback(&mut state, &self.state);
+                            }
+                        } else {
+                            if state.is_paused {
+                                if ui
+                                    .add_enabled(
+                                        !capturing,
                                         egui::Button::new("▶️ Resume")
                                             .min_size(btn_size)
                                             .fill(egui::Color32::from_rgb(50, 180, 100)),
@@ -412,7 +1401,8 @@ impl App for SkySheetApp {
                                 }
                             } else {
                                 if ui
-                                    .add(
+                                    .add_enabled(
+                                        !capturing,
                                         egui::Button::new("⏸️ Pause")
                                             .min_size(btn_size)
                                             .fill(egui::Color32::from_rgb(180, 180, 50)),
@@ -427,19 +1417,30 @@ impl App for SkySheetApp {
                             ui.add_space(10.0);
 
                             if ui
-                                .add(
+                                .add_enabled(
+                                    !capturing,
                                     egui::Button::new("⏹️ Stop")
                                         .min_size(btn_size)
                                         .fill(egui::Color32::from_rgb(180, 50, 50)),
                                 )
                                 .clicked()
                             {
-                                state.is_playing = false;
-                                state.is_paused = false;
-                                state.status = "Stopped".to_string();
+                                stop_playback(&mut state);
                             }
                         }
 
+                        ui.add_space(10.0);
+
+                        if ui
+                            .add_enabled(
+                                state.song_path.is_some() && !capturing,
+                                egui::Button::new("⏮ Restart").min_size(btn_size),
+                            )
+                            .clicked()
+                        {
+                            restart_song(&mut state);
+                        }
+
                         ui.add_space(20.0);
 
                         // Add a vertical separator
@@ -458,10 +1459,7 @@ impl App for SkySheetApp {
                                     )
                                     .clicked()
                                 {
-                                    state.speed -= 0.1;
-                                    if state.speed < 0.5 {
-                                        state.speed = 0.5;
-                                    }
+                                    state.speed = clamp_speed(&state, state.speed - 0.1);
                                 }
 
                                 ui.add(egui::Label::new(format!("{:.1}x", state.speed)));
@@ -473,10 +1471,7 @@ impl App for SkySheetApp {
                                     )
                                     .clicked()
                                 {
-                                    state.speed += 0.1;
-                                    if state.speed > 2.0 {
-                                        state.speed = 2.0;
-                                    }
+                                    state.speed = clamp_speed(&state, state.speed + 0.1);
                                 }
                             });
                         });
@@ -485,11 +1480,373 @@ impl App for SkySheetApp {
                     ui.add_space(5.0);
 
                     // Speed slider below the buttons
+                    let max_speed = state.max_speed;
                     ui.add(
-                        egui::Slider::new(&mut state.speed, 0.5..=2.0)
+                        egui::Slider::new(&mut state.speed, MIN_SPEED..=max_speed)
                             .text("Speed")
                             .show_value(false),
                     );
+                    ui.horizontal(|ui| {
+                        for preset in SPEED_PRESETS.into_iter().filter(|&p| p <= max_speed) {
+                            let selected = (state.speed - preset).abs() < 0.001;
+                            if ui
+                                .selectable_label(selected, format!("{}x", preset))
+                                .clicked()
+                            {
+                                state.speed = clamp_speed(&state, preset);
+                                state.status = format!("Speed: {}x", preset);
+                            }
+                        }
+                    });
+
+                    ui.checkbox(
+                        &mut state.preview_mode,
+                        "👁 Preview (log notes instead of sending keys)",
+                    );
+
+                    // Looping only applies to automatic playback
+                    ui.add_enabled_ui(!state.manual_mode, |ui| {
+                        if ui
+                            .checkbox(&mut state.loop_enabled, "🔁 Loop until stopped")
+                            .changed()
+                        {
+                            save_app_config(&mut state);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut changed = ui
+                            .add_enabled(
+                                !state.manual_mode,
+                                egui::Checkbox::new(&mut state.auto_play, "Auto-play on load"),
+                            )
+                            .changed();
+                        changed |= ui
+                            .checkbox(&mut state.hold_last_chord, "Hold the last chord")
+                            .on_hover_text("Let the final chord ring before releasing it")
+                            .changed();
+                        changed |= ui
+                            .checkbox(&mut state.sustain_chords, "Sustain shared keys")
+                            .on_hover_text(
+                                "Keys the next chord also plays stay down instead of being \
+                                 tapped again; only the keys that change are pressed or released",
+                            )
+                            .changed();
+                        if changed {
+                            save_app_config(&mut state);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let (hide_label, hide_hint) = if self.tray.is_some() {
+                            (
+                                "Hide to tray",
+                                "Hotkeys keep working while hidden; \
+                                 bring the window back with the tray icon's Show window item",
+                            )
+                        } else {
+                            (
+                                "Minimize",
+                                "Hotkeys keep working while minimized; \
+                                 restore the window from the taskbar",
+                            )
+                        };
+                        if ui
+                            .checkbox(
+                                &mut state.minimize_on_play,
+                                format!("{} when playing", hide_label),
+                            )
+                            .on_hover_text(hide_hint)
+                            .changed()
+                        {
+                            save_app_config(&mut state);
+                        }
+                        if ui.button(format!("🗕 {} now", hide_label)).clicked() {
+                            hide_window(ctx, self.tray.is_some());
+                        }
+                        if ui
+                            .checkbox(&mut state.finish_alert, "🔔 Alert when done")
+                            .on_hover_text("Chime and flash the taskbar when a song finishes")
+                            .changed()
+                        {
+                            save_app_config(&mut state);
+                        }
+                        if ui
+                            .checkbox(&mut state.show_overlay, "📌 Now playing overlay")
+                            .on_hover_text("A small window that stays above the game")
+                            .changed()
+                        {
+                            save_app_config(&mut state);
+                        }
+                        if ui
+                            .checkbox(&mut state.always_on_top, "Always on top")
+                            .on_hover_text("Keep this window above the game")
+                            .changed()
+                        {
+                            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                                window_level(state.always_on_top),
+                            ));
+                            save_app_config(&mut state);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut state.metronome_enabled, "Metronome");
+                        ui.add_enabled(
+                            state.metronome_enabled,
+                            egui::DragValue::new(&mut state.metronome_subdivision)
+                                .range(1..=4)
+                                .prefix("clicks per beat: "),
+                        );
+                        if let Some(song) = state.songs.get(state.active_song) {
+                            ui.label(format!("({} bpm)", song.bpm));
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Lead-in:");
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut state.lead_in_secs)
+                                    .range(0..=10)
+                                    .suffix(" s"),
+                            )
+                            .on_hover_text("Countdown before the first note, to switch to the game")
+                            .changed()
+                        {
+                            save_app_config(&mut state);
+                        }
+                    });
+
+                    ui.horizontal(|ui| {
+                        ui.label("Target window:");
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut state.target_window)
+                                .hint_text("focused window"),
+                        );
+                        if response.lost_focus() {
+                            save_app_config(&mut state);
+                        }
+                    });
+
+                    ui.collapsing("Practice", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("Fastest speed:");
+                            if ui
+                                .add(
+                                    egui::DragValue::new(&mut state.max_speed)
+                                        .range(2.0..=MAX_SPEED_LIMIT)
+                                        .speed(0.1)
+                                        .suffix("x"),
+                                )
+                                .changed()
+                            {
+                                state.speed = clamp_speed(&state, state.speed);
+                                state.ramp_start = clamp_speed(&state, state.ramp_start);
+                                state.ramp_target = clamp_speed(&state, state.ramp_target);
+                                save_app_config(&mut state);
+                            }
+                        });
+                        if ui
+                            .checkbox(&mut state.ramp_enabled, "Speed ramp on each replay")
+                            .changed()
+                        {
+                            reset_speed_ramp(&mut state);
+                        }
+                        let max_speed = state.max_speed;
+                        ui.horizontal(|ui| {
+                            ui.label("Start:");
+                            ui.add(
+                                egui::DragValue::new(&mut state.ramp_start)
+                                    .range(MIN_SPEED..=max_speed)
+                                    .speed(0.05)
+                                    .suffix("x"),
+                            );
+                            ui.label("Step:");
+                            ui.add(
+                                egui::DragValue::new(&mut state.ramp_step)
+                                    .range(0.01..=0.5)
+                                    .speed(0.01)
+                                    .suffix("x"),
+                            );
+                            ui.label("Target:");
+                            ui.add(
+                                egui::DragValue::new(&mut state.ramp_target)
+                                    .range(MIN_SPEED..=max_speed)
+                                    .speed(0.05)
+                                    .suffix("x"),
+                            );
+                        });
+                    });
+
+                    ui.collapsing("Folder cleanup", |ui| {
+                        ui.label("Checks every sheet in a folder and reports what loading fixes.");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut state.batch_rewrite, "Rewrite files")
+                                .on_hover_text(
+                                    "Save each readable sheet back sorted and pretty-printed, \
+                                     replacing the original",
+                                );
+                            if ui.button("📁 Check folder...").clicked()
+                                && let Some(dir) = FileDialog::new().pick_folder()
+                            {
+                                check_sheet_folder(&mut state, &dir);
+                            }
+                        });
+                        for line in &state.batch_report {
+                            ui.label(line);
+                        }
+                    });
+
+                    ui.collapsing("Instrument timing", |ui| {
+                        let mut changed = false;
+                        egui::ComboBox::from_label("Preset")
+                            .selected_text(state.instrument_preset.name())
+                            .show_ui(ui, |ui| {
+                                for preset in enum_iterator::all::<InstrumentPreset>() {
+                                    changed |= ui
+                                        .selectable_value(
+                                            &mut state.instrument_preset,
+                                            preset,
+                                            preset.name(),
+                                        )
+                                        .changed();
+                                }
+                            });
+                        if let Some(profile) = state.instrument_preset.profile() {
+                            state.timing = profile;
+                        }
+                        // Individual values are only editable in the Custom preset
+                        let custom = state.instrument_preset == InstrumentPreset::Custom;
+                        ui.add_enabled_ui(custom, |ui| {
+                            let timing = &mut state.timing;
+                            for (value, label) in [
+                                (&mut timing.hold_important_ms, "Accent hold"),
+                                (&mut timing.hold_peak_ms, "Peak hold"),
+                                (&mut timing.hold_normal_ms, "Normal hold"),
+                                (&mut timing.gap_important_ms, "Accent gap"),
+                                (&mut timing.gap_normal_ms, "Normal gap"),
+                                (&mut timing.variation_ms, "Random variation"),
+                            ] {
+                                changed |= ui
+                                    .add(
+                                        egui::Slider::new(value, 0..=200).text(label).suffix(" ms"),
+                                    )
+                                    .changed();
+                            }
+                        });
+                        ui.label("Holds and gaps are at 1x and shrink or grow with the speed.");
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut state.legato, 0.0..=1.0)
+                                    .text("Staccato ↔ legato")
+                                    .custom_formatter(|v, _| format!("{:.0}%", v * 100.0)),
+                            )
+                            .on_hover_text(
+                                "How much of the time until the next chord each key is held; \
+                                 0% uses only the holds above",
+                            )
+                            .changed();
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut state.repeat_gap_ms, 0..=100)
+                                    .text("Same-key repeat gap")
+                                    .suffix(" ms"),
+                            )
+                            .on_hover_text(
+                                "A repeated key stays up at least this long so the game sees \
+                                 two presses. Unlike the gaps above it doesn't scale with speed, \
+                                 so very fast repeats may be delayed slightly.",
+                            )
+                            .changed();
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut state.min_hold_ms, 0..=100)
+                                    .text("Minimum hold")
+                                    .suffix(" ms"),
+                            )
+                            .on_hover_text(
+                                "Every key stays down at least this long, in automatic and \
+                                 manual play, so the game doesn't drop short presses. In fast \
+                                 passages this eats into the gap before the next note, so \
+                                 keep it as low as the game allows.",
+                            )
+                            .changed();
+                        changed |= ui
+                            .add(
+                                egui::Slider::new(&mut state.timing_jitter_ms, 0..=50)
+                                    .text("Timing jitter")
+                                    .suffix(" ms"),
+                            )
+                            .on_hover_text(
+                                "Presses each chord up to this much early or late at random. \
+                                 It never exceeds half the time to a neighbouring chord, \
+                                 and the song's overall tempo doesn't drift.",
+                            )
+                            .changed();
+                        if changed {
+                            save_app_config(&mut state);
+                        }
+                    });
+
+                    ui.collapsing("Latency calibration", |ui| {
+                        ui.label(
+                            "Taps Shift 40 times, 25 ms apart, and measures how long each \
+                             keyboard call takes and how late it goes out.",
+                        );
+                        ui.horizontal(|ui| {
+                            let can_measure = !state.is_playing && !state.measuring_latency;
+                            if ui
+                                .add_enabled(can_measure, egui::Button::new("⏱ Measure"))
+                                .clicked()
+                            {
+                                measure_latency(&mut state, &self.state);
+                            }
+                            if state.measuring_latency {
+                                ui.spinner();
+                            }
+                        });
+                        if let Some(report) = state.latency {
+                            ui.label(report.to_string());
+                            let suggested = report.suggested_min_hold_ms().min(100);
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Suggested minimum hold: {} ms", suggested));
+                                if ui
+                                    .add_enabled(
+                                        state.min_hold_ms != suggested,
+                                        egui::Button::new("Use it"),
+                                    )
+                                    .clicked()
+                                {
+                                    state.min_hold_ms = suggested;
+                                    save_app_config(&mut state);
+                                }
+                            });
+                        }
+                    });
+
+                    ui.collapsing("Humanization", |ui| {
+                        let mut changed = ui
+                            .checkbox(
+                                &mut state.expressive_timing,
+                                "Expressive timing (accents and random variation)",
+                            )
+                            .on_hover_text(
+                                "Turn off for fixed-length presses using the normal hold and gap",
+                            )
+                            .changed();
+                        ui.horizontal(|ui| {
+                            let mut fixed_seed = state.humanize_seed.is_some();
+                            if ui.checkbox(&mut fixed_seed, "Fixed random seed").changed() {
+                                state.humanize_seed = fixed_seed.then_some(0);
+                                changed = true;
+                            }
+                            if let Some(seed) = state.humanize_seed.as_mut() {
+                                changed |= ui.add(egui::DragValue::new(seed)).changed();
+                            }
+                        });
+                        if changed {
+                            save_app_config(&mut state);
+                        }
+                    });
                 });
             });
 
@@ -501,321 +1858,1889 @@ impl App for SkySheetApp {
                     ui.strong("Status: ");
                     ui.label(&state.status);
                 });
-                if state.total > 0 {
-                    ui.add_space(5.0);
+                ui.horizontal(|ui| {
+                    ui.strong("Global hotkeys: ");
+                    if state.song_path.is_none() {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(200, 120, 120),
+                            "inactive — select a song first",
+                        );
+                    } else if !has_played(&state) {
+                        ui.colored_label(
+                            egui::Color32::from_rgb(220, 200, 110),
+                            format!(
+                                "{} only — press Play once to enable the rest",
+                                format_key_description(state.hotkeys.play_pause)
+                            ),
+                        );
+                    } else {
+                        ui.colored_label(egui::Color32::from_rgb(120, 200, 140), "active");
+                    }
+                });
+                let held = state.held_keys.lock().unwrap().clone();
+                ui.horizontal(|ui| {
+                    ui.strong("Keys down: ");
+                    if held.is_empty() {
+                        ui.weak("none");
+                    }
+                    for key in held {
+                        ui.label(
+                            egui::RichText::new(key_name(key))
+                                .monospace()
+                                .strong()
+                                .background_color(egui::Color32::from_rgb(60, 130, 90)),
+                        );
+                    }
+                });
+                if let Some(song) = state.songs.get(state.active_song) {
+                    let position = if state.manual_mode {
+                        state.manual_index
+                    } else {
+                        state.progress
+                    };
+                    draw_note_lane(ui, &song.song_notes, position, &note_mapping(&state));
+
+                    // Times are in real seconds at the current speed, from the first note
+                    // when the lead-in is trimmed
+                    let start = if state.trim_lead_in {
+                        lead_in_ms(song)
+                    } else {
+                        0
+                    };
+                    let speed = if state.manual_mode {
+                        1.0
+                    } else {
+                        playable_speed(state.speed)
+                    };
+                    let at = |index: usize| {
+                        let time = song.song_notes.get(index).map_or(0, |note| note.time);
+                        (time.saturating_sub(start) as f32 / speed) as u64
+                    };
+                    let total = at(song.song_notes.len().saturating_sub(1));
+                    let elapsed = if position == 0 {
+                        0
+                    } else {
+                        at(position - 1).min(total)
+                    };
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "⏱ {} / {}  (-{})",
+                            format_mm_ss(elapsed),
+                            format_mm_ss(total),
+                            format_mm_ss(total - elapsed)
+                        ));
+                        ui.separator();
+                        ui.label(format!("♩ = {} bpm", song.bpm));
+                        if !state.manual_mode && state.speed != 1.0 {
+                            ui.label(format!("(playing at {:.0})", song.bpm as f32 * state.speed));
+                        }
+                    });
+                }
+                if state.preview_mode || !state.preview_log.is_empty() {
+                    ui.collapsing("Preview log", |ui| {
+                        if ui.button("Clear").clicked() {
+                            state.preview_log.clear();
+                        }
+                        egui::ScrollArea::vertical()
+                            .max_height(120.0)
+                            .stick_to_bottom(true)
+                            .show(ui, |ui| {
+                                for line in &state.preview_log {
+                                    ui.monospace(line);
+                                }
+                            });
+                    });
+                }
+                ui.collapsing("Test keys", |ui| {
+                    ui.label(
+                        "Check your layout without the game: with Preview on, playing types \
+                         the mapped characters here.",
+                    );
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut state.test_keys, "Type preview into this field");
+                        if ui.button("Type all 15 keys").clicked() {
+                            let mapping = note_mapping(&state);
+                            let typed: String = (0..GRID_SIZE)
+                                .filter_map(unmap_key)
+                                .filter_map(|key| map_key(&key, &mapping))
+                                .filter_map(|key| match key {
+                                    Key::Unicode(c) => Some(c),
+                                    _ => None,
+                                })
+                                .collect();
+                            state.test_keys_text.push_str(&typed);
+                        }
+                        if ui.button("Clear").clicked() {
+                            state.test_keys_text.clear();
+                        }
+                    });
                     ui.add(
-                        egui::ProgressBar::new(state.progress as f32 / state.total as f32)
-                            .text(format!("{}/{} notes", state.progress, state.total)),
+                        egui::TextEdit::multiline(&mut state.test_keys_text)
+                            .desired_rows(2)
+                            .desired_width(f32::INFINITY)
+                            .hint_text("1Key0 to 1Key14 should come out in grid order"),
+                    );
+                });
+                let note_count = state
+                    .songs
+                    .get(state.active_song)
+                    .map_or(0, |song| song.song_notes.len());
+                if note_count > 0 {
+                    ui.add_space(5.0);
+                    if !state.scrubbing {
+                        state.scrub_position = if state.manual_mode {
+                            state.manual_index
+                        } else {
+                            state.progress.saturating_sub(1)
+                        }
+                        .min(note_count - 1);
+                    }
+                    // The scrubber doubles as the progress display
+                    let played = if state.manual_mode {
+                        state.manual_index
+                    } else {
+                        state.progress
+                    };
+                    let response = ui.add(
+                        egui::Slider::new(&mut state.scrub_position, 0..=note_count - 1)
+                            .text(format!("{}/{} notes", played, note_count))
+                            .show_value(false),
                     );
+                    state.scrubbing = response.dragged();
+                    if state.scrubbing {
+                        let note = &state.songs[state.active_song].song_notes[state.scrub_position];
+                        let key = map_key(&note.key, &note_mapping(&state))
+                            .map_or("?".to_string(), key_name);
+                        ui.label(format!("Note {}: {}", state.scrub_position + 1, key));
+                    }
+                    if response.drag_stopped() || (response.changed() && !response.dragged()) {
+                        let position = state.scrub_position;
+                        seek_to(&mut state, position);
+                    }
+
+                    let playhead = if state.manual_mode {
+                        state.manual_index
+                    } else {
+                        state.progress.saturating_sub(1)
+                    };
+                    let songs = Arc::clone(&state.songs);
+                    if let Some(index) = draw_timeline(
+                        ui,
+                        &songs[state.active_song].song_notes,
+                        playhead,
+                        loop_region(&state),
+                    ) {
+                        seek_to(&mut state, index);
+                    }
+
+                    // A-B practice loop over part of the song
+                    ui.add_enabled_ui(!state.manual_mode, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.label("A-B loop:");
+                            if ui.button("Set A").clicked() {
+                                state.loop_start = Some(playhead);
+                                state.status = format!("Loop start set to note {}", playhead + 1);
+                            }
+                            if ui.button("Set B").clicked() {
+                                state.loop_end = Some(playhead);
+                                state.status = format!("Loop end set to note {}", playhead + 1);
+                            }
+                            if ui
+                                .add_enabled(
+                                    state.loop_start.is_some() || state.loop_end.is_some(),
+                                    egui::Button::new("Clear"),
+                                )
+                                .clicked()
+                            {
+                                state.loop_start = None;
+                                state.loop_end = None;
+                            }
+                            let describe = |point: Option<usize>| {
+                                point.map_or("–".to_string(), |index| (index + 1).to_string())
+                            };
+                            ui.label(format!(
+                                "A: {}  B: {}",
+                                describe(state.loop_start),
+                                describe(state.loop_end)
+                            ));
+                            if let (Some(start), Some(end)) = (state.loop_start, state.loop_end)
+                                && start > end
+                            {
+                                ui.colored_label(
+                                    egui::Color32::from_rgb(220, 200, 110),
+                                    "A must come before B",
+                                );
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Start at note:");
+                            ui.add(
+                                egui::DragValue::new(&mut state.start_note)
+                                    .range(1..=note_count)
+                                    .speed(1),
+                            )
+                            .on_hover_text("Play begins here; an A-B loop starts at A instead");
+                            if ui
+                                .add_enabled(
+                                    state.start_note > 1,
+                                    egui::Button::new("From the top"),
+                                )
+                                .clicked()
+                            {
+                                state.start_note = 1;
+                            }
+                        });
+                    });
                 }
             });
         });
     }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Threads die with the process, so make sure nothing is left pressed in the game
+        let mapping = {
+            let mut state = self.state.lock().unwrap();
+            state.is_playing = false;
+            state.manual_armed = false;
+            // Speed and window size change too often to save on every change
+            if let Err(e) = app_config::save_config(&app_config::AppConfig::from(&*state)) {
+                eprintln!("Failed to save settings: {}", e);
+            }
+            note_mapping(&state)
+        };
+        match EnigoOutput::new() {
+            Ok(mut output) => release_all_keys(&mut output, &mapping),
+            Err(e) => eprintln!("{} for the release on exit", e),
+        }
+        debug_log::flush();
+    }
+}
+
+// Puts hotkeys, or everything, back to defaults. Hotkeys alone can be undone;
+// a full reset deletes the saved files and keeps only the song that's open.
+fn reset_settings(state: &mut AppState, reset: SettingsReset) {
+    remember_hotkeys(state);
+    state.hotkeys = Hotkeys::default();
+    if reset == SettingsReset::Hotkeys {
+        save_hotkey_config(state);
+        state.status = "Hotkeys reset to defaults".to_string();
+        return;
+    }
+    state.hotkey_history.clear();
+    let song_path = state.song_path.take();
+    app_config::AppConfig::default().apply(state);
+    state.song_path = song_path;
+    // The defaults turn debug logging off, so the log file has to stop too
+    let logging = debug_log::set_enabled(state.debug_logging);
+    state.status = match hotkey_config::delete_hotkeys()
+        .and(app_config::delete_config())
+        .and(logging)
+    {
+        Ok(()) => "All settings reset to defaults".to_string(),
+        Err(e) => e,
+    };
+}
+
+// Saves the current bindings so the next change can be undone
+fn remember_hotkeys(state: &mut AppState) {
+    let previous = state.hotkeys.clone();
+    state.hotkey_history.push(previous);
+    if state.hotkey_history.len() > HOTKEY_UNDO_LIMIT {
+        state.hotkey_history.remove(0);
+    }
+}
+
+// Stores a captured key in the given hotkey slot, keeping the old set for undo
+fn apply_hotkey(state: &mut AppState, slot: HotkeyCapture, combo: HotkeyCombo) {
+    remember_hotkeys(state);
+    match slot {
+        HotkeyCapture::WaitingForPlayPause => {
+            state.hotkeys.play_pause = combo;
+            state.status = format!(
+                "Play/Pause hotkey set to: {}",
+                format_key_description(combo)
+            );
+        }
+        HotkeyCapture::WaitingForStop => {
+            state.hotkeys.stop = combo;
+            state.status = format!("Stop hotkey set to: {}", format_key_description(combo));
+        }
+        HotkeyCapture::WaitingForSpeedUp => {
+            state.hotkeys.speed_up = combo;
+            state.status = format!("Speed Up hotkey set to: {}", format_key_description(combo));
+        }
+        HotkeyCapture::WaitingForSpeedDown => {
+            state.hotkeys.speed_down = combo;
+            state.status = format!(
+                "Speed Down hotkey set to: {}",
+                format_key_description(combo)
+            );
+        }
+        HotkeyCapture::WaitingForPanic => {
+            state.hotkeys.panic = combo;
+            state.status = format!("Panic hotkey set to: {}", format_key_description(combo));
+        }
+        HotkeyCapture::WaitingForManualAdvance => {
+            state.hotkeys.manual_advance = combo;
+            state.status = format!(
+                "Manual advance key set to: {}",
+                format_key_description(combo)
+            );
+        }
+        HotkeyCapture::WaitingForManualAdvanceAlt => {
+            state.hotkeys.manual_advance_alt = combo;
+            state.status = format!(
+                "Second manual advance key set to: {}",
+                format_key_description(combo)
+            );
+        }
+        HotkeyCapture::WaitingForManualRetrigger => {
+            state.hotkeys.manual_retrigger = combo;
+            state.status = format!(
+                "Manual replay key set to: {}",
+                format_key_description(combo)
+            );
+        }
+        HotkeyCapture::WaitingForRestart => {
+            state.hotkeys.restart = combo;
+            state.status = format!("Restart hotkey set to: {}", format_key_description(combo));
+        }
+        HotkeyCapture::None => return,
+    }
+    save_hotkey_config(state);
+}
+
+// Why a captured combo can't be used for the given slot, if it can't
+fn hotkey_conflict(state: &AppState, slot: &HotkeyCapture, combo: HotkeyCombo) -> Option<String> {
+    let description = format_key_description(combo);
+    if !is_valid_hotkey(combo.key) {
+        return Some(format!(
+            "{} is reserved and can't be used as a hotkey",
+            description
+        ));
+    }
+    let hotkeys = &state.hotkeys;
+    let bindings = [
+        (
+            HotkeyCapture::WaitingForPlayPause,
+            hotkeys.play_pause,
+            "Play/Pause",
+        ),
+        (HotkeyCapture::WaitingForStop, hotkeys.stop, "Stop"),
+        (
+            HotkeyCapture::WaitingForSpeedUp,
+            hotkeys.speed_up,
+            "Speed Up",
+        ),
+        (
+            HotkeyCapture::WaitingForSpeedDown,
+            hotkeys.speed_down,
+            "Speed Down",
+        ),
+        (HotkeyCapture::WaitingForPanic, hotkeys.panic, "Panic"),
+        (HotkeyCapture::WaitingForRestart, hotkeys.restart, "Restart"),
+        (
+            HotkeyCapture::WaitingForManualAdvance,
+            hotkeys.manual_advance,
+            "Manual advance",
+        ),
+        (
+            HotkeyCapture::WaitingForManualAdvanceAlt,
+            hotkeys.manual_advance_alt,
+            "Manual advance",
+        ),
+        (
+            HotkeyCapture::WaitingForManualRetrigger,
+            hotkeys.manual_retrigger,
+            "Manual replay",
+        ),
+    ];
+    bindings
+        .iter()
+        .find(|(other_slot, bound, _)| other_slot != slot && *bound == combo)
+        .map(|(_, _, action)| format!("{} is already bound to {}", description, action))
+}
+
+// Whether pressing this key would also be sent as one of the note outputs
+fn is_note_output_key(keycode: Keycode, mapping: &NoteMapping) -> bool {
+    note_outputs(mapping).any(|key| hotkey_utils::key_to_keycode(key) == Some(keycode))
+}
+
+// Every key playback can send: the layout's grid, then the keys of the other banks
+fn note_outputs(mapping: &NoteMapping) -> impl Iterator<Item = Key> + '_ {
+    let extra_banks = mapping.layout.extra_bank_keys().into_iter().flatten();
+    mapping
+        .keys
+        .iter()
+        .copied()
+        .chain(extra_banks.map(|&c| Key::Unicode(c)))
+}
+
+// Runs the latency calibration on a worker thread with a fresh keyboard connection,
+// the same kind automatic playback uses
+fn measure_latency(state: &mut AppState, state_arc: &Arc<Mutex<AppState>>) {
+    state.measuring_latency = true;
+    state.status = "Measuring keyboard latency...".to_string();
+    let state_arc = Arc::clone(state_arc);
+    thread::spawn(move || {
+        let result = EnigoOutput::new().map(|mut output| calibration::measure(&mut output));
+        let mut state = state_arc.lock().unwrap();
+        state.measuring_latency = false;
+        match result {
+            Ok(report) => {
+                debug_log::log(format!("Latency calibration: {}", report));
+                state.status = "Latency measured".to_string();
+                state.latency = Some(report);
+            }
+            Err(e) => state.status = e,
+        }
+    });
+}
+
+// Launches the automatic playback worker
+fn start_playback(state: &mut AppState, state_arc: &Arc<Mutex<AppState>>) {
+    state.is_playing = true;
+    state.playback_active = true;
+    // The listener thread can start playback too, and only update() can hide the window
+    state.minimize_pending = state.minimize_on_play;
+    state.status = "Starting playback...".to_string();
+    let state_arc = Arc::clone(state_arc);
+    std::thread::spawn(move || {
+        play_song_gui(Arc::clone(&state_arc));
+        state_arc.lock().unwrap().playback_active = false;
+    });
+}
+
+// Opens a file the user chose, remembering it for next time and starting auto-play
+fn open_song_file(state: &mut AppState, state_arc: &Arc<Mutex<AppState>>, path: &str) {
+    load_song_in_background(state, state_arc, path, true);
+}
+
+// Parses a song file on a worker thread so a big or broken file doesn't freeze the
+// window. The result is dropped if another file was opened in the meantime.
+fn load_song_in_background(
+    state: &mut AppState,
+    state_arc: &Arc<Mutex<AppState>>,
+    path: &str,
+    auto_play: bool,
+) {
+    state.loading_path = Some(path.to_string());
+    state.status = format!("Loading {}…", path);
+    let dedupe = state.dedupe_notes;
+    let state_arc = Arc::clone(state_arc);
+    let path = path.to_string();
+    thread::spawn(move || {
+        let loaded = load_songs(&path, dedupe);
+        let mut state = state_arc.lock().unwrap();
+        if state.loading_path.as_deref() != Some(path.as_str()) {
+            return;
+        }
+        state.loading_path = None;
+        apply_loaded_songs(&mut state, &path, loaded);
+        save_app_config(&mut state);
+        state.auto_play_pending =
+            auto_play && state.auto_play && !state.manual_mode && !state.songs.is_empty();
+    });
+}
+
+// Reads a song file into the state right away; for the command line, which has no
+// window to keep responsive
+fn open_song_now(state: &mut AppState, path: &str) {
+    let loaded = load_songs(path, state.dedupe_notes);
+    apply_loaded_songs(state, path, loaded);
+}
+
+// Banks 2 and 3 only have keys on QWERTY, so other layouts skip those notes
+fn extra_bank_warning(layout: KeyLayout, songs: &[Song]) -> Option<String> {
+    let skipped = songs
+        .iter()
+        .flat_map(|song| &song.song_notes)
+        .filter(|note| sheet_key(&note.key).is_some_and(|(bank, _)| bank > 0))
+        .count();
+    (skipped > 0 && layout.extra_bank_keys().iter().all(|bank| bank.is_empty())).then(|| {
+        format!(
+            "{} notes are in banks 2 and 3, which the {} layout has no keys for",
+            skipped,
+            layout.name()
+        )
+    })
+}
+
+// Puts freshly parsed songs in the state, caching them for playback, and resets
+// everything tied to the previous song
+fn apply_loaded_songs(state: &mut AppState, path: &str, loaded: Result<LoadedSongs, String>) {
+    state.song_path = Some(path.to_string());
+    match loaded {
+        Ok(mut loaded) => {
+            if let Some(warning) = extra_bank_warning(state.key_layout, &loaded.songs) {
+                loaded.warnings.push(warning);
+            }
+            let summary = loaded.songs.first().map_or_else(
+                || "Loaded an empty file".to_string(),
+                |song| format!("Loaded {}", song_summary(song)),
+            );
+            let others = loaded.songs.len().saturating_sub(1);
+            let summary = match others {
+                0 => summary,
+                1 => format!("{} (+1 more song)", summary),
+                _ => format!("{} (+{} more songs)", summary, others),
+            };
+            let summary = format!("{} · {}", summary, loaded.format);
+            state.status = if loaded.warnings.is_empty() {
+                summary
+            } else {
+                format!("{} ({})", summary, loaded.warnings.join(", "))
+            };
+            state.songs = Arc::new(loaded.songs);
+            state.songs_path = state.song_path.clone();
+            remember_recent_song(state, path);
+        }
+        Err(e) => {
+            state.status = e;
+            state.songs = Arc::new(Vec::new());
+            state.songs_path = None;
+        }
+    }
+    reset_song_state(state);
+    reset_speed_ramp(state);
+    state.is_playing = false;
+}
+
+// Makes a playlist entry the current song, as if it had been opened and selected.
+// Playback has already put its songs in the cache.
+fn enter_playlist_entry(state: &mut AppState, path: &str, song_index: usize) {
+    if state.song_path.as_deref() == Some(path) && state.selected_song == song_index {
+        return;
+    }
+    state.song_path = Some(path.to_string());
+    reset_song_state(state);
+    state.selected_song = song_index;
+    state.transpose = state
+        .songs
+        .get(song_index)
+        .map_or(0, |song| song.pitch_level);
+}
+
+// Clears everything tied to the previous song once a new one is in `songs`
+fn reset_song_state(state: &mut AppState) {
+    state.selected_song = 0;
+    state.active_song = 0;
+    state.transpose = state.songs.first().map_or(0, |song| song.pitch_level);
+    state.seek_target = None;
+    state.medley_included = vec![true; state.songs.len()];
+    state.manual_index = 0; // Reset manual index on new song
+    state.loop_start = None;
+    state.loop_end = None;
+    state.start_note = 1;
+    state.tap_accuracy.reset();
+    state.help_text_draft = None;
+    state.manual_armed = state.manual_mode; // Manual mode is ready right after a new song
+    state.progress = 0;
+    state.total = state.songs.first().map_or(0, |song| song.song_notes.len());
+}
+
+// "'Name' — 842 notes, ~3:12", enough to tell whether the right sheet was picked
+fn song_summary(song: &Song) -> String {
+    let length = song.song_notes.last().map_or(0, |note| note.time);
+    format!(
+        "'{}' — {} notes, ~{}",
+        song.name,
+        song.song_notes.len(),
+        format_mm_ss(length)
+    )
+}
+
+// Writes the edited notes into the selected song and saves the whole file back
+fn save_help_text(state: &mut AppState) {
+    let (Some(path), Some(draft)) = (&state.song_path, &state.help_text_draft) else {
+        return;
+    };
+    let mut songs = state.songs.as_ref().clone();
+    let Some(song) = songs.get_mut(state.selected_song) else {
+        return;
+    };
+    song.help_text = draft.clone();
+    // The draft stays open if the write fails so nothing typed is lost
+    match write_song_file(path, &songs) {
+        Ok(()) => {
+            state.songs = Arc::new(songs);
+            state.help_text_draft = None;
+            state.status = "Sheet notes saved".to_string();
+        }
+        Err(e) => state.status = e,
+    }
+}
+
+// Moves a successfully loaded file to the top of the library, keeping its pin
+fn remember_recent_song(state: &mut AppState, path: &str) {
+    let name = state
+        .songs
+        .first()
+        .map_or_else(|| path.to_string(), |song| song.name.clone());
+    let pinned = state
+        .recent_songs
+        .iter()
+        .any(|entry| entry.path == path && entry.pinned);
+    state.recent_songs.retain(|entry| entry.path != path);
+    state.recent_songs.insert(
+        0,
+        app_config::RecentSong {
+            path: path.to_string(),
+            name,
+            pinned,
+        },
+    );
+    let mut unpinned = 0;
+    state.recent_songs.retain(|entry| {
+        if !entry.pinned {
+            unpinned += 1;
+        }
+        entry.pinned || unpinned <= RECENT_SONGS_LIMIT
+    });
+}
+
+// Keeps a speed inside what the controls allow
+fn clamp_speed(state: &AppState, speed: f32) -> f32 {
+    playable_speed(speed).clamp(MIN_SPEED, state.max_speed)
+}
+
+// Restarts the practice ramp from its start speed
+fn reset_speed_ramp(state: &mut AppState) {
+    state.ramp_pending_start = true;
+    if state.ramp_enabled {
+        state.speed = state.ramp_start;
+    }
+}
+
+// Called whenever playback (re)starts: the first run uses the start speed,
+// each later run adds a step until the target is reached
+fn advance_speed_ramp(state: &mut AppState) {
+    if !state.ramp_enabled {
+        return;
+    }
+    if state.ramp_pending_start {
+        state.speed = state.ramp_start;
+        state.ramp_pending_start = false;
+    } else {
+        state.speed = (state.speed + state.ramp_step).min(state.ramp_target.max(state.ramp_start));
+    }
+}
+
+// Thin strip showing where notes cluster across the whole song, with a playhead
+// at the given note. Returns the note index to seek to when clicked.
+fn draw_timeline(
+    ui: &mut egui::Ui,
+    notes: &[Note],
+    playhead: usize,
+    loop_region: Option<(usize, usize)>,
+) -> Option<usize> {
+    let width = ui.available_width();
+    let (rect, response) = ui.allocate_exact_size(egui::vec2(width, 16.0), egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_rgb(30, 30, 48));
+
+    let end_time = notes.last().map_or(0, |note| note.time).max(1) as f32;
+    let x_for = |time: u64| rect.left() + rect.width() * (time as f32 / end_time).min(1.0);
+
+    // Bucket notes into 2px columns and shade each by how busy it is
+    let bins = ((rect.width() / 2.0) as usize).max(1);
+    let mut counts = vec![0u32; bins];
+    for note in notes {
+        let bin = ((note.time as f32 / end_time) * (bins - 1) as f32) as usize;
+        counts[bin.min(bins - 1)] += 1;
+    }
+    let busiest = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+    for (bin, &count) in counts.iter().enumerate() {
+        if count == 0 {
+            continue;
+        }
+        let left = rect.left() + bin as f32 * 2.0;
+        let strength = 0.25 + 0.75 * (count as f32 / busiest);
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(left, rect.top()),
+                egui::pos2(left + 2.0, rect.bottom()),
+            ),
+            0.0,
+            egui::Color32::from_rgb(100, 140, 255).gamma_multiply(strength),
+        );
+    }
+
+    if let Some((start, end)) = loop_region
+        && let (Some(a), Some(b)) = (notes.get(start), notes.get(end))
+    {
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(x_for(a.time), rect.top()),
+                egui::pos2(x_for(b.time).max(x_for(a.time) + 2.0), rect.bottom()),
+            ),
+            0.0,
+            egui::Color32::from_rgba_unmultiplied(120, 220, 140, 60),
+        );
+    }
+
+    if let Some(note) = notes.get(playhead) {
+        let x = x_for(note.time);
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            egui::Stroke::new(2.0, egui::Color32::from_rgb(255, 220, 120)),
+        );
+    }
+
+    let pointer = response
+        .interact_pointer_pos()
+        .filter(|_| response.clicked())?;
+    let time = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0) * end_time;
+    let index = notes.partition_point(|note| (note.time as f32) < time);
+    // An empty song has nothing to seek to
+    notes.len().checked_sub(1).map(|last| index.min(last))
+}
+
+// Moves playback to a note index; the worker picks it up on its next check
+fn seek_to(state: &mut AppState, index: usize) {
+    if state.manual_mode {
+        state.manual_index = index;
+        state.progress = index;
+        state.tap_accuracy.restart();
+    } else {
+        // Playback picks this up on its next step, or when resumed or started;
+        // the count updates right away so a paused seek shows where it will resume
+        state.seek_target = Some(index);
+        state.progress = index + 1;
+    }
+    state.status = format!("Seeked to note {}", index + 1);
+}
+
+// Whether Play runs a medley or the playlist rather than the selected song
+fn plays_several_songs(state: &AppState) -> bool {
+    state.medley_mode || (state.playlist_mode && !state.playlist.is_empty())
+}
+
+// The A-B practice loop, when both points are set in order and automatic playback
+// is on a single song
+fn loop_region(state: &AppState) -> Option<(usize, usize)> {
+    if state.manual_mode || plays_several_songs(state) {
+        return None;
+    }
+    match (state.loop_start, state.loop_end) {
+        (Some(start), Some(end)) if start <= end => Some((start, end)),
+        _ => None,
+    }
+}
+
+// Jumps back to the first note; running playback carries on from there, even if paused
+fn restart_song(state: &mut AppState) {
+    state.manual_index = 0;
+    state.progress = 0;
+    if state.is_playing && !state.manual_mode {
+        state.seek_target = Some(0);
+        state.is_paused = false;
+        state.status = "Restarted from the beginning".to_string();
+    } else {
+        state.seek_target = None;
+        state.status = "Back to the beginning".to_string();
+    }
+}
+
+// Whether playback has started at least once for the current song
+fn has_played(state: &AppState) -> bool {
+    state.is_playing || state.manual_armed || state.progress > 0
+}
+
+// Arming lets manual taps play notes; disarming keeps the position for later
+fn set_manual_armed(state: &mut AppState, armed: bool) {
+    state.manual_armed = armed;
+    state.tap_accuracy.restart();
+    if !armed {
+        state.manual_key_down = false;
+    }
+    state.status = if armed {
+        format!("Manual mode armed at note {}", state.manual_index + 1)
+    } else {
+        format!("Manual mode disarmed at note {}", state.manual_index + 1)
+    };
+}
+
+fn save_app_config(state: &mut AppState) {
+    match app_config::save_config(&app_config::AppConfig::from(&*state)) {
+        Ok(()) => push_toast(state, "Settings saved"),
+        Err(e) => state.status = e,
+    }
+}
+
+fn save_hotkey_config(state: &mut AppState) {
+    match hotkey_config::save_hotkeys(&state.hotkeys) {
+        Ok(()) => push_toast(state, "Hotkeys saved"),
+        Err(e) => state.status = e,
+    }
+}
+
+// Keeps the preview log bounded on long or looping runs
+fn push_preview_log(state: &mut AppState, line: String) {
+    state.preview_log.push(line);
+    if state.preview_log.len() > PREVIEW_LOG_LIMIT {
+        let excess = state.preview_log.len() - PREVIEW_LOG_LIMIT;
+        state.preview_log.drain(..excess);
+    }
+}
+
+// Shows a message for a couple of seconds without touching the status line.
+// Repeats of the newest toast just refresh it, so sliders don't stack copies.
+fn push_toast(state: &mut AppState, message: &str) {
+    match state.toasts.last_mut() {
+        Some((last, posted)) if last == message => *posted = Instant::now(),
+        _ => state.toasts.push((message.to_string(), Instant::now())),
+    }
+}
+
+// Shares the parsed songs with a worker, parsing again only if they belong to another file
+fn cached_songs(state_arc: &Arc<Mutex<AppState>>, path: &str) -> Result<Arc<Vec<Song>>, String> {
+    let dedupe = {
+        let state = state_arc.lock().unwrap();
+        if !state.songs.is_empty() && state.songs_path.as_deref() == Some(path) {
+            return Ok(Arc::clone(&state.songs));
+        }
+        state.dedupe_notes
+    };
+    // Parse outside the lock so the UI and hotkeys stay responsive
+    let mut songs = load_song_from_path(path)?;
+    if dedupe {
+        dedupe_notes(&mut songs);
+    }
+    let songs = Arc::new(songs);
+    let mut state = state_arc.lock().unwrap();
+    state.songs = Arc::clone(&songs);
+    state.songs_path = Some(path.to_string());
+    Ok(songs)
+}
+
+// Oldest preview log lines are dropped past this many
+const PREVIEW_LOG_LIMIT: usize = 500;
+
+// How many chords the note lane shows, including the current one
+const LANE_CHORDS: usize = 10;
+
+// The chord just played followed by the next few, as their key labels and whether
+// each is the current one. `position` is the next note to play, so the one before
+// it is current. Runs every frame, so it looks at no more than LANE_CHORDS note
+// groups even when some of them map to no key.
+fn lane_chords(notes: &[Note], position: usize, mapping: &NoteMapping) -> Vec<(bool, Vec<String>)> {
+    let current = position.checked_sub(1).filter(|&i| i < notes.len());
+    let mut index = match current {
+        Some(i) => notes[..i].partition_point(|note| note.time < notes[i].time),
+        None => position,
+    };
+    let mut chords = Vec::new();
+    for _ in 0..LANE_CHORDS {
+        let Some(time) = notes.get(index).map(|note| note.time) else {
+            break;
+        };
+        let is_current = current.is_some_and(|i| notes[i].time == time);
+        let mut labels = Vec::new();
+        while index < notes.len() && notes[index].time == time {
+            if let Some(key) = map_key(&notes[index].key, mapping) {
+                labels.push(key_name(key));
+            }
+            index += 1;
+        }
+        if !labels.is_empty() {
+            chords.push((is_current, labels));
+        }
+    }
+    chords
+}
+
+// Each lane chord as a column of its keys, the current one highlighted
+fn draw_note_lane(ui: &mut egui::Ui, notes: &[Note], position: usize, mapping: &NoteMapping) {
+    let chords = lane_chords(notes, position, mapping);
+    if chords.is_empty() {
+        return;
+    }
+
+    ui.horizontal(|ui| {
+        for (is_current, labels) in chords {
+            let fill = if is_current {
+                egui::Color32::from_rgb(90, 80, 40)
+            } else {
+                egui::Color32::from_rgb(40, 40, 62)
+            };
+            egui::Frame::group(ui.style()).fill(fill).show(ui, |ui| {
+                ui.vertical(|ui| {
+                    for label in labels {
+                        let text = egui::RichText::new(label).monospace().strong();
+                        ui.label(if is_current {
+                            text.color(egui::Color32::from_rgb(255, 220, 120))
+                        } else {
+                            text
+                        });
+                    }
+                });
+            });
+        }
+    });
 }
 
 fn play_song_gui(state_arc: Arc<Mutex<AppState>>) {
     // We'll use this function to safely get a lock and handle errors
     let get_lock = || state_arc.lock().unwrap();
 
-    // Initial setup - get file path and speed
-    let (path, speed, _total_notes) = {
+    // Initial setup - get file path; speed is re-read for every note
+    let (path, playlist) = {
         let mut state = get_lock();
         state.is_playing = true;
         state.status = "Playing...".to_string();
 
-        // Get path
-        let path = match &state.song_path {
-            Some(p) => p.clone(),
-            None => {
+        // A playlist carries its own paths; otherwise get the open file's path
+        let playlist = (state.playlist_mode && !state.playlist.is_empty()).then(|| {
+            state
+                .playlist
+                .iter()
+                .map(|(path, index)| (path.to_string_lossy().into_owned(), *index))
+                .collect::<Vec<_>>()
+        });
+        let path = match (&playlist, &state.song_path) {
+            (Some(playlist), _) => playlist[0].0.clone(),
+            (None, Some(p)) => p.clone(),
+            (None, None) => {
                 state.status = "No song file selected!".to_string();
                 state.is_playing = false;
                 return;
             }
         };
 
-        let speed = state.speed;
-        (path, speed, 0)
+        advance_speed_ramp(&mut state);
+        debug_log::log(format!("Playback started: {} at {:.2}x", path, state.speed));
+        (path, playlist)
     };
-
-    // Read the song file
-    let mut file = match File::open(&path) {
-        Ok(f) => f,
-        Err(e) => {
-            let mut state = get_lock();
-            state.status = format!("Failed to open file: {}", e);
-            state.is_playing = false;
-            return;
+    let is_playlist = playlist.is_some();
+
+    // A playlist runs its entries in order, a medley every included song in the
+    // file, otherwise just the selected one
+    let run: Vec<(String, usize)> = match playlist {
+        Some(playlist) => playlist,
+        None => {
+            // Use the songs parsed at load time, reading the file only if nothing is cached
+            let songs = match cached_songs(&state_arc, &path) {
+                Ok(songs) => songs,
+                Err(e) => {
+                    let mut state = get_lock();
+                    state.status = e;
+                    state.is_playing = false;
+                    return;
+                }
+            };
+            let state = get_lock();
+            if state.medley_mode {
+                (0..songs.len())
+                    .filter(|&i| state.medley_included.get(i).copied().unwrap_or(true))
+                    .map(|i| (path.clone(), i))
+                    .collect()
+            } else if let Some(last) = songs.len().checked_sub(1) {
+                vec![(path.clone(), state.selected_song.min(last))]
+            } else {
+                // A MIDI or community import can come out with no songs at all
+                drop(state);
+                let mut state = get_lock();
+                state.status = SongError::NoSongs.to_string();
+                state.is_playing = false;
+                return;
+            }
         }
     };
-
-    // Read file contents
-    let mut contents = String::new();
-    if let Err(e) = file.read_to_string(&mut contents) {
+    let medley_gap = Duration::from_secs_f32(get_lock().medley_gap_secs.max(0.0));
+    if run.is_empty() {
         let mut state = get_lock();
-        state.status = format!("Failed to read file: {}", e);
+        state.status = "No songs selected for the medley!".to_string();
         state.is_playing = false;
         return;
     }
 
-    // Parse JSON
-    let song = match serde_json::from_str::<Vec<Song>>(&contents) {
-        Ok(songs) if !songs.is_empty() => songs[0].clone(),
-        _ => {
-            let mut state = get_lock();
-            state.status =
-                "Invalid song format! JSON must contain at least one Song object.".to_string();
-            state.is_playing = false;
-            return;
+    // Bring the configured game window forward before any keys are sent
+    let target_window = get_lock().target_window.trim().to_string();
+    if !target_window.is_empty() && !get_lock().preview_mode {
+        match window_target::focus_window(&target_window) {
+            Ok(()) => thread::sleep(Duration::from_millis(150)),
+            Err(e) => get_lock().status = format!("{}; sending to the focused window", e),
+        }
+    }
+
+    // Count down so there's time to switch to the game, still honoring Stop
+    let lead_in_secs = get_lock().lead_in_secs;
+    for remaining in (1..=lead_in_secs).rev() {
+        get_lock().status = format!("Starting in {}…", remaining);
+        let second_start = Instant::now();
+        while second_start.elapsed() < Duration::from_secs(1) {
+            thread::sleep(Duration::from_millis(50));
+            if !get_lock().is_playing {
+                get_lock().status = "Stopped".to_string();
+                return;
+            }
+        }
+    }
+    if lead_in_secs > 0 {
+        get_lock().status = "Playing...".to_string();
+    }
+
+    // Initialize keyboard emulator; a preview run only logs the keys, or types them
+    // into the Test Keys field
+    let (preview, test_keys) = {
+        let state = get_lock();
+        (state.preview_mode, state.test_keys)
+    };
+    let output: Box<dyn KeyOutput> = if preview && test_keys {
+        let text_state = Arc::clone(&state_arc);
+        Box::new(TextOutput::new(move |c| {
+            text_state.lock().unwrap().test_keys_text.push(c)
+        }))
+    } else if preview {
+        let log_state = Arc::clone(&state_arc);
+        Box::new(LoggingOutput::new(move |line| {
+            push_preview_log(&mut log_state.lock().unwrap(), line)
+        }))
+    } else {
+        match EnigoOutput::new() {
+            Ok(output) => Box::new(output),
+            Err(e) => {
+                let mut state = get_lock();
+                state.status = e;
+                state.is_playing = false;
+                return;
+            }
         }
     };
+    let mut output = TrackedOutput::new(output, Arc::clone(&get_lock().held_keys));
+
+    let mut replay = 0;
+    loop {
+        let mut played_any = false;
+        for (position, (path, song_index)) in run.iter().enumerate() {
+            let song_index = *song_index;
+            // Pause between medley entries (and before a medley replays), still honoring Stop
+            if position > 0 || (replay > 0 && run.len() > 1) {
+                get_lock().status = "Next song coming up...".to_string();
+                let gap_start = Instant::now();
+                while gap_start.elapsed() < medley_gap {
+                    thread::sleep(Duration::from_millis(100));
+                    if !get_lock().is_playing {
+                        get_lock().status = "Stopped".to_string();
+                        return;
+                    }
+                }
+            }
 
-    // Initialize keyboard emulator
-    let mut enigo = match Enigo::new(&Settings::default()) {
-        Ok(e) => e,
-        Err(e) => {
+            // Playlist entries can be in other files, or gone since they were added
+            let songs = match cached_songs(&state_arc, path) {
+                Ok(songs) if song_index < songs.len() => songs,
+                result => {
+                    let reason = result
+                        .err()
+                        .unwrap_or_else(|| "song not in file".to_string());
+                    debug_log::log(format!("Skipping {} #{}: {}", path, song_index + 1, reason));
+                    get_lock().status = format!("Skipped {}: {}", path, reason);
+                    continue;
+                }
+            };
+
+            {
+                let mut state = get_lock();
+                if is_playlist {
+                    enter_playlist_entry(&mut state, path, song_index);
+                }
+                state.active_song = song_index;
+                if run.len() > 1 {
+                    state.status = format!(
+                        "Playing {}/{}: {}",
+                        position + 1,
+                        run.len(),
+                        songs[song_index].name
+                    );
+                }
+            }
+
+            played_any = true;
+            if !play_notes(&state_arc, &songs[song_index], &mut output) {
+                return;
+            }
+        }
+        if !played_any {
             let mut state = get_lock();
-            state.status = format!("Failed to initialize keyboard: {}", e);
+            state.status = "Nothing in the playlist could be played".to_string();
             state.is_playing = false;
             return;
         }
-    };
 
-    // Set up RNG and timing
-    let mut rng = rand::rng();
-    let start_time = Instant::now();
+        // Start over from the first note while loop mode is on
+        {
+            let mut state = get_lock();
+            if !state.loop_enabled || state.manual_mode || !state.is_playing {
+                break;
+            }
+            replay += 1;
+            state.progress = 0;
+            state.status = format!("Looping (pass {})...", replay + 1);
+            advance_speed_ramp(&mut state);
+            debug_log::log(format!("Loop {} at {:.2}x", replay, state.speed));
+        }
+    }
 
-    // Update total note count
-    {
+    // Song finished
+    debug_log::log("Playback finished");
+    debug_log::flush();
+    let mut state = get_lock();
+    announce_finish(&mut state);
+    state.is_playing = false;
+}
+
+// Reports the end of a song, with a chime and a taskbar flash when they're on
+fn announce_finish(state: &mut AppState) {
+    state.status = "Song finished!".to_string();
+    if state.finish_alert {
+        metronome::play_chime();
+        state.attention_pending = true;
+    }
+}
+
+// Plays one song's notes, returning false if playback was stopped part way
+// Keys are pressed and released with the state unlocked, since the output may log into it
+fn play_notes(state_arc: &Arc<Mutex<AppState>>, song: &Song, output: &mut dyn KeyOutput) -> bool {
+    let get_lock = || state_arc.lock().unwrap();
+
+    let options = {
         let mut state = get_lock();
+        // Update total note count
         state.total = song.song_notes.len();
         state.progress = 0;
+        let timing = if state.expressive_timing {
+            state.timing
+        } else {
+            state.timing.literal()
+        };
+        PlayerOptions {
+            timing,
+            mapping: note_mapping(&state),
+            trim_lead_in: state.trim_lead_in,
+            hold_last_chord: state.hold_last_chord,
+            sustain_shared_keys: state.sustain_chords,
+            humanize_seed: state.humanize_seed,
+            time_offset_ms: state.time_offset_ms,
+        }
+    };
+    let mut player = Player::new(song, output, options);
+    player.play(Instant::now());
+    // With an A-B loop set, practice starts at A; otherwise at the chosen start note,
+    // which plays right away (after any lead-in countdown)
+    let start = {
+        let state = get_lock();
+        loop_region(&state)
+            .map(|(start, _)| start)
+            .or_else(|| (!plays_several_songs(&state)).then(|| state.start_note.saturating_sub(1)))
+    };
+    if let Some(start) = start.filter(|&start| start > 0) {
+        player.seek(start, Instant::now());
     }
+    let mut paused = false;
 
-    // Play each note
-    for (index, note) in song.song_notes.iter().enumerate() {
-        // Check if we need to stop or pause
-        let should_play = {
+    loop {
+        // Check if we need to stop, seek or pause
+        let settings = {
             let mut state = get_lock();
 
             // Check if playback should stop
             if !state.is_playing {
                 state.status = "Stopped".to_string();
-                return;
+                drop(state);
+                debug_log::log(format!("Stopped at note {}", player.position() + 1));
+                player.stop();
+                debug_log::flush();
+                return false;
             }
 
-            // Update progress
-            state.progress = index + 1;
+            // Jump to a requested note, which then plays immediately
+            if let Some(target) = state.seek_target.take() {
+                player.seek(target, Instant::now());
+                debug_log::log(format!("Seek to note {}", player.position() + 1));
+            }
 
-            // Handle pause if needed
+            // Wait with every key up until we're unpaused or stopped
             if state.is_paused {
-                state.status = "Paused".to_string();
+                let pausing = !paused;
+                if pausing {
+                    state.status = "Paused".to_string();
+                }
                 drop(state); // Release lock while paused
-
-                // Wait until we're unpaused or stopped
-                loop {
-                    thread::sleep(Duration::from_millis(100));
-
-                    let state = get_lock();
-                    if !state.is_playing {
-                        return; // Stop playback
-                    }
-
-                    if !state.is_paused {
-                        break; // Resume playback
-                    }
-
-                    drop(state); // Release lock for next iteration
+                if pausing {
+                    paused = true;
+                    player.pause();
+                    debug_log::log(format!("Paused at note {}", player.position() + 1));
+                    debug_log::flush();
                 }
-
-                // Set status to playing again
-                let mut state = get_lock();
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            }
+            if paused {
+                paused = false;
+                // Picks up any seek made while paused too
+                player.play(Instant::now());
                 state.status = "Playing...".to_string();
+                debug_log::log(format!("Resumed at note {}", player.position() + 1));
             }
 
-            true
-        };
-
-        if !should_play {
-            return;
-        }
-
-        // Calculate timing
-        let adjusted_time = (note.time as f32 / speed) as u64;
-        let target_time = Duration::from_millis(adjusted_time);
-        let elapsed = start_time.elapsed();
-
-        // Wait until the right moment to play this note
-        if elapsed < target_time {
-            thread::sleep(target_time - elapsed);
-        }
-
-        // Play the note if we have a valid keyboard mapping
-        if let Some(key) = map_key(&note.key) {
-            // Determine note characteristics
-            let is_important = index % 4 == 0;
-            let is_melodic_peak = index > 0
-                && index < song.song_notes.len() - 1
-                && note.time > song.song_notes[index - 1].time
-                && (index == song.song_notes.len() - 1
-                    || note.time > song.song_notes[index + 1].time);
-
-            // Set note duration based on importance
-            let base_hold = if is_important {
-                55
-            } else if is_melodic_peak {
-                50
-            } else {
-                35
-            };
+            // Once B has played, carry on from A after the gap that would have followed B
+            if let Some((start, end)) = loop_region(&state)
+                && player.position() > end
+            {
+                let gap = song.song_notes.get(end + 1).map_or_else(
+                    || 60_000 / u64::from(song.bpm.max(1)),
+                    |next| next.time - song.song_notes[end].time,
+                );
+                player.jump_back(start, gap);
+                debug_log::log(format!("A-B loop back to note {}", start + 1));
+            }
 
-            // Add a small variation to hold duration for a more natural sound
-            let variation = rng.random_range(-5..=5);
-            let hold_duration = Duration::from_millis((base_hold + variation) as u64);
+            state.progress = (player.position() + 1).min(song.song_notes.len());
 
-            // Press and release the key
-            let _ = enigo.key(Key::Unicode(key), Press);
-            thread::sleep(hold_duration);
-            let _ = enigo.key(Key::Unicode(key), Release);
+            // Pick up speed changes from the slider or hotkeys as we go; since
+            // notes are timed from the previous one, only the rest of the song changes
+            LiveSettings {
+                speed: state.speed,
+                legato: state.legato,
+                repeat_gap: Duration::from_millis(state.repeat_gap_ms),
+                timing_jitter: Duration::from_millis(state.timing_jitter_ms),
+                min_hold: Duration::from_millis(state.min_hold_ms),
+            }
+        };
 
-            // Brief articulation gap between notes
-            let gap = if is_important { 5 } else { 10 };
-            thread::sleep(Duration::from_millis(gap));
+        let now = Instant::now();
+        match player.tick(now, &settings) {
+            // Short slices so stop, pause and seek requests are picked up during long rests
+            Step::Wait(until) => thread::sleep(
+                until
+                    .saturating_duration_since(now)
+                    .min(Duration::from_millis(20)),
+            ),
+            Step::Played(chord) => {
+                let labels = chord
+                    .keys
+                    .iter()
+                    .map(|&key| key_name(key))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                debug_log::log(format!(
+                    "Notes {}-{} -> {} ({} ms late, hold {} ms)",
+                    chord.notes.start + 1,
+                    chord.notes.end,
+                    labels,
+                    chord.late.as_millis(),
+                    chord.hold.as_millis()
+                ));
+            }
+            Step::Finished => return true,
         }
     }
+}
 
-    // Song finished
-    let mut state = get_lock();
-    state.status = "Song finished!".to_string();
-    state.is_playing = false;
+fn format_mm_ss(ms: u64) -> String {
+    let secs = ms / 1000;
+    format!("{}:{:02}", secs / 60, secs % 60)
 }
 
-fn play_song_manual_tick(state_arc: Arc<Mutex<AppState>>) {
+fn lead_in_ms(song: &Song) -> u64 {
+    song.song_notes.first().map_or(0, |note| note.time)
+}
+
+// Plays the next chord, or with `advance` off replays the last one in place
+fn play_song_manual_tick(state_arc: Arc<Mutex<AppState>>, advance: bool) {
     // Get song path and manual index
-    let (path, manual_index) = {
+    let (path, manual_index, selected_song) = {
         let state = state_arc.lock().unwrap();
         match (&state.song_path, state.manual_index) {
-            (Some(p), idx) => (p.clone(), idx),
+            (Some(p), idx) => (p.clone(), idx, state.selected_song),
             _ => return,
         }
     };
 
-    // Read file
-    let mut file = match File::open(&path) {
-        Ok(f) => f,
-        Err(_) => return,
+    // Reporting problems matters here since a silent failure looks like a dead key
+    let songs = match cached_songs(&state_arc, &path) {
+        Ok(songs) => songs,
+        Err(e) => {
+            state_arc.lock().unwrap().status = e;
+            return;
+        }
     };
-    let mut contents = String::new();
-    if file.read_to_string(&mut contents).is_err() {
+    let Some(song) = songs.get(selected_song).or_else(|| songs.last()) else {
+        state_arc.lock().unwrap().status = SongError::NoSongs.to_string();
         return;
-    }
-    let contents = contents.trim();
-    let song = match serde_json::from_str::<Vec<Song>>(contents) {
-        Ok(songs) if !songs.is_empty() => songs[0].clone(),
-        _ => return,
     };
+    if !advance {
+        // Retrigger: the chord that ends right before the current index
+        if manual_index == 0 || manual_index > song.song_notes.len() {
+            return;
+        }
+        let group_start = player::chord_start(song, manual_index);
+        debug_log::log(format!(
+            "Manual retrigger: notes {}..{}",
+            group_start + 1,
+            manual_index
+        ));
+        press_manual_chord(&state_arc, song, group_start..manual_index);
+        return;
+    }
     if manual_index >= song.song_notes.len() {
         let mut state = state_arc.lock().unwrap();
         state.status = "Song finished!".to_string();
-        state.is_playing = false;
+        state.manual_armed = false;
         return;
     }
     // Find all notes at the next time
-    let next_time = song.song_notes[manual_index].time;
-    let mut notes_to_play = Vec::new();
-    let mut new_index = manual_index;
-    while new_index < song.song_notes.len() && song.song_notes[new_index].time == next_time {
-        notes_to_play.push(song.song_notes[new_index].clone());
-        new_index += 1;
-    }
+    let new_index = player::chord_end(song, manual_index);
+    // The tap time is taken before pressing, since a held chord blocks until key up
+    let tapped_at = Instant::now();
     // Play all notes at this time
-    let mut enigo = match Enigo::new(&Settings::default()) {
-        Ok(e) => e,
-        Err(_) => return,
-    };
-    for note in &notes_to_play {
-        if let Some(key) = map_key(&note.key) {
-            let _ = enigo.key(Key::Unicode(key), Press);
-            thread::sleep(Duration::from_millis(40));
-            let _ = enigo.key(Key::Unicode(key), Release);
-        }
-    }
+    debug_log::log(format!(
+        "Manual advance: notes {}..{}",
+        manual_index + 1,
+        new_index
+    ));
+    press_manual_chord(&state_arc, song, manual_index..new_index);
     // Update progress and index
     let mut state = state_arc.lock().unwrap();
+    if state.recording {
+        let start = *state.record_start.get_or_insert(tapped_at);
+        let time = tapped_at.duration_since(start).as_millis() as u64;
+        for note in &song.song_notes[manual_index..new_index] {
+            state.recorded_notes.push(Note {
+                time,
+                ..note.clone()
+            });
+        }
+    }
+    if state.score_taps {
+        let speed = state.speed;
+        let note_time = song.song_notes[manual_index].time;
+        state
+            .tap_accuracy
+            .record(manual_index, note_time, tapped_at, speed);
+    }
     state.progress = new_index;
     state.manual_index = new_index;
     state.total = song.song_notes.len();
     if new_index >= song.song_notes.len() {
-        state.status = "Song finished!".to_string();
-        state.is_playing = false;
+        announce_finish(&mut state);
+        state.manual_armed = false;
     } else {
         state.status = format!("Manual: {}/{} notes", new_index, song.song_notes.len());
     }
 }
 
+// Saves the selected song as a MIDI file for use in other tools
+fn export_midi(state: &mut AppState) {
+    let songs = Arc::clone(&state.songs);
+    let Some(song) = songs.get(state.selected_song) else {
+        return;
+    };
+    let Some(path) = FileDialog::new()
+        .add_filter("MIDI", &["mid"])
+        .set_file_name(format!("{}.mid", song.name))
+        .save_file()
+    else {
+        return;
+    };
+    state.status = match midi::write_midi_file(&path.display().to_string(), song) {
+        Ok(()) => format!("Exported MIDI to {}", path.display()),
+        Err(e) => e,
+    };
+}
+
+// Runs the folder cleanup and lists the results under its button
+fn check_sheet_folder(state: &mut AppState, dir: &std::path::Path) {
+    let report = match normalize_folder(dir, state.batch_rewrite, state.dedupe_notes) {
+        Ok(report) => report,
+        Err(e) => {
+            state.status = e;
+            return;
+        }
+    };
+    state.status = format!(
+        "Checked {} sheets: {} ok, {} failed{}",
+        report.ok + report.failed.len(),
+        report.ok,
+        report.failed.len(),
+        if state.batch_rewrite {
+            format!(", {} rewritten", report.rewritten)
+        } else {
+            String::new()
+        }
+    );
+    state.batch_report = report
+        .failed
+        .iter()
+        .map(|(name, e)| format!("✖ {}: {}", name, e))
+        .chain(
+            report
+                .fixed
+                .iter()
+                .map(|(name, fixes)| format!("✔ {}: {}", name, fixes.join(", "))),
+        )
+        .collect();
+    if state.batch_report.is_empty() {
+        state
+            .batch_report
+            .push("Every sheet loaded cleanly".to_string());
+    }
+}
+
+// Asks where to save the recorded taps, keeping the source song's metadata
+fn save_recording(state: &mut AppState) {
+    if state.recorded_notes.is_empty() {
+        return;
+    }
+    let Some(source) = state.songs.get(state.selected_song) else {
+        return;
+    };
+    let song = Song {
+        song_notes: state.recorded_notes.clone(),
+        ..source.clone()
+    };
+    let Some(path) = FileDialog::new()
+        .add_filter("Song sheets", SHEET_EXTENSIONS)
+        .set_file_name(format!("{} (recorded).txt", song.name))
+        .save_file()
+    else {
+        state.status = "Recording kept; use Save recording to write it".to_string();
+        return;
+    };
+    match write_song_file(&path.display().to_string(), &[song]) {
+        Ok(()) => {
+            state.status = format!("Recording saved to {}", path.display());
+            state.recorded_notes.clear();
+            state.record_start = None;
+        }
+        Err(e) => state.status = e,
+    }
+}
+
+fn press_manual_chord(state_arc: &Arc<Mutex<AppState>>, song: &Song, notes: Range<usize>) {
+    let mapping = note_mapping(&state_arc.lock().unwrap());
+    let keys = player::chord_keys(song, notes, &mapping);
+
+    {
+        let mut state = state_arc.lock().unwrap();
+        if state.preview_mode && state.test_keys {
+            for &key in &keys {
+                if let Key::Unicode(c) = key {
+                    state.test_keys_text.push(c);
+                }
+            }
+            return;
+        }
+        if state.preview_mode {
+            let labels: Vec<String> = keys.iter().map(|&key| key_name(key)).collect();
+            let line = format!("manual  #{}  {}", state.manual_index + 1, labels.join(" "));
+            push_preview_log(&mut state, line);
+            return;
+        }
+    }
+
+    // Connect once and reuse it, which keeps taps snappy
+    let (existing, held_keys) = {
+        let state = state_arc.lock().unwrap();
+        (state.manual_output.clone(), Arc::clone(&state.held_keys))
+    };
+    let mut output = match existing.map_or_else(SharedOutput::spawn, Ok) {
+        Ok(output) => {
+            state_arc.lock().unwrap().manual_output = Some(output.clone());
+            TrackedOutput::new(Box::new(output), held_keys)
+        }
+        Err(e) => {
+            state_arc.lock().unwrap().status = format!("{}; manual taps can't send keys", e);
+            return;
+        }
+    };
+
+    let (manual_hold, min_hold) = {
+        let state = state_arc.lock().unwrap();
+        (state.manual_hold, Duration::from_millis(state.min_hold_ms))
+    };
+    if manual_hold {
+        let pressed_at = Instant::now();
+        for &key in &keys {
+            output.press(key);
+        }
+        // Keep the chord down until the advance key comes back up or playback stops
+        loop {
+            thread::sleep(Duration::from_millis(5));
+            let state = state_arc.lock().unwrap();
+            if !state.manual_key_down || !state.manual_armed || !state.manual_mode {
+                break;
+            }
+        }
+        // A quick tap still holds long enough to register
+        thread::sleep(min_hold.saturating_sub(pressed_at.elapsed()));
+        for &key in &keys {
+            output.release(key);
+        }
+    } else {
+        for &key in &keys {
+            output.press(key);
+            thread::sleep(Duration::from_millis(40).max(min_hold));
+            output.release(key);
+        }
+    }
+}
+
+// Sends a release for every note key, in every bank, and modifier in case any got
+// stuck down
+fn release_all_keys(output: &mut dyn KeyOutput, mapping: &NoteMapping) {
+    for key in note_outputs(mapping) {
+        output.release(key);
+    }
+    for modifier in [Key::Shift, Key::Control, Key::Alt] {
+        output.release(modifier);
+    }
+}
+
 fn main() {
+    // `--play` runs a song without opening the window
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(cli::CliAction::Play(options)) => {
+            if let Err(e) = cli::run(options) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(cli::CliAction::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(cli::CliAction::Gui) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+
+    let (config, _) = app_config::load_config();
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([650.0, 550.0]),
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size(config.window_size.unwrap_or([650.0, 550.0]))
+            .with_window_level(window_level(config.always_on_top)),
         ..Default::default()
     };
 
     let _ = eframe::run_native(
         "Sky Sheet Player",
         options,
-        Box::new(|_cc| Ok(Box::new(SkySheetApp::default()))),
+        Box::new(|cc| Ok(Box::new(SkySheetApp::new(&cc.egui_ctx)))),
     );
 }
 
-fn map_key(key_str: &str) -> Option<char> {
-    if let Some(key_num) = key_str.strip_prefix("1Key") {
-        if let Ok(num) = key_num.parse::<u32>() {
-            return match num {
-                0 => Some('y'),
-                1 => Some('u'),
-                2 => Some('i'),
-                3 => Some('o'),
-                4 => Some('p'),
-                5 => Some('h'),
-                6 => Some('j'),
-                7 => Some('k'),
-                8 => Some('l'),
-                9 => Some(';'),
-                10 => Some('n'),
-                11 => Some('m'),
-                12 => Some('.'),
-                13 => Some(','),
-                14 => Some('/'),
-                _ => None,
-            };
-        }
+// Gets the window out of the way: into the tray if there is one, else the taskbar
+fn hide_window(ctx: &egui::Context, has_tray: bool) {
+    if has_tray {
+        ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+    } else {
+        ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(true));
     }
-    None
+}
+
+fn window_level(always_on_top: bool) -> egui::WindowLevel {
+    if always_on_top {
+        egui::WindowLevel::AlwaysOnTop
+    } else {
+        egui::WindowLevel::Normal
+    }
+}
+
+// Note characters for the layout currently selected
+fn note_keys(state: &AppState) -> NoteKeys {
+    match state.key_layout {
+        KeyLayout::Custom => state.custom_keys,
+        layout => layout.note_keys(),
+    }
+}
+
+// Everything needed to turn a sheet key into a keyboard key
+#[derive(Debug, Clone, Copy)]
+struct NoteMapping {
+    keys: NoteKeys,
+    transpose: i32,
+    // Decides whether banks 2 and 3 have keys
+    layout: KeyLayout,
+}
+
+fn note_mapping(state: &AppState) -> NoteMapping {
+    NoteMapping {
+        keys: note_keys(state),
+        transpose: state.transpose,
+        layout: state.key_layout,
+    }
+}
+
+// Resolves a sheet key to the keyboard key to send; an enigo Key rather than a
+// char so non-character outputs like Return or the arrows are possible.
+// Notes transposed off either end of the grid are skipped. Banks past the first
+// ("2KeyN", "3KeyN") play on their own fixed keys, on QWERTY only.
+fn map_key(key_str: &str, mapping: &NoteMapping) -> Option<Key> {
+    let (bank, index) = sheet_key(key_str)?;
+    let shifted = usize::try_from(index + mapping.transpose).ok()?;
+    match bank {
+        0 => mapping.keys.get(shifted).copied(),
+        bank => mapping.layout.extra_bank_keys()[bank - 1]
+            .get(shifted)
+            .map(|&c| Key::Unicode(c)),
+    }
+}
+
+// Sets the transpose to the octave shift that keeps the most of the active song's notes
+fn fit_song_to_range(state: &mut AppState) {
+    let Some(song) = state.songs.get(state.active_song) else {
+        state.status = "No song loaded".to_string();
+        return;
+    };
+    let positions: Vec<i32> = song
+        .song_notes
+        .iter()
+        .filter_map(|note| sheet_position(&note.key))
+        .collect();
+    let (shift, outside) = fit_transpose(&positions);
+    state.transpose = shift;
+    state.status = if outside == 0 {
+        format!("Transposed by {}; every note fits", shift)
+    } else {
+        format!(
+            "Transposed by {}; {} notes are still out of range and will be skipped",
+            shift, outside
+        )
+    };
 }
 
 fn rdev_key_to_keycode(key: RdevKey) -> Option<Keycode> {
     use device_query::Keycode as DKey;
     use rdev::Key as RKey;
     Some(match key {
-        RKey::Space => DKey::Space,
-        RKey::Escape => DKey::Escape,
-        RKey::Equal => DKey::Equal,
-        RKey::Minus => DKey::Minus,
-        RKey::SemiColon => DKey::Semicolon,
-        RKey::Quote => DKey::Apostrophe,
+        RKey::KeyA => DKey::A,
+        RKey::KeyB => DKey::B,
+        RKey::KeyC => DKey::C,
+        RKey::KeyD => DKey::D,
+        RKey::KeyE => DKey::E,
+        RKey::KeyF => DKey::F,
+        RKey::KeyG => DKey::G,
         RKey::KeyH => DKey::H,
+        RKey::KeyI => DKey::I,
         RKey::KeyJ => DKey::J,
         RKey::KeyK => DKey::K,
         RKey::KeyL => DKey::L,
-        RKey::KeyN => DKey::N,
         RKey::KeyM => DKey::M,
+        RKey::KeyN => DKey::N,
         RKey::KeyO => DKey::O,
         RKey::KeyP => DKey::P,
+        RKey::KeyQ => DKey::Q,
+        RKey::KeyR => DKey::R,
+        RKey::KeyS => DKey::S,
+        RKey::KeyT => DKey::T,
         RKey::KeyU => DKey::U,
-        RKey::KeyI => DKey::I,
+        RKey::KeyV => DKey::V,
+        RKey::KeyW => DKey::W,
+        RKey::KeyX => DKey::X,
         RKey::KeyY => DKey::Y,
+        RKey::KeyZ => DKey::Z,
+        RKey::Num0 => DKey::Key0,
+        RKey::Num1 => DKey::Key1,
+        RKey::Num2 => DKey::Key2,
+        RKey::Num3 => DKey::Key3,
+        RKey::Num4 => DKey::Key4,
+        RKey::Num5 => DKey::Key5,
+        RKey::Num6 => DKey::Key6,
+        RKey::Num7 => DKey::Key7,
+        RKey::Num8 => DKey::Key8,
+        RKey::Num9 => DKey::Key9,
+        RKey::UpArrow => DKey::Up,
+        RKey::DownArrow => DKey::Down,
+        RKey::LeftArrow => DKey::Left,
+        RKey::RightArrow => DKey::Right,
+        RKey::Space => DKey::Space,
+        RKey::Escape => DKey::Escape,
+        RKey::Return => DKey::Enter,
+        RKey::Backspace => DKey::Backspace,
+        RKey::Insert => DKey::Insert,
+        RKey::Delete => DKey::Delete,
+        RKey::Home => DKey::Home,
+        RKey::End => DKey::End,
+        RKey::PageUp => DKey::PageUp,
+        RKey::PageDown => DKey::PageDown,
+        RKey::Equal => DKey::Equal,
+        RKey::Minus => DKey::Minus,
+        RKey::SemiColon => DKey::Semicolon,
+        RKey::Quote => DKey::Apostrophe,
         RKey::Comma => DKey::Comma,
         RKey::Dot => DKey::Dot,
         RKey::Slash => DKey::Slash,
-        // Add more as needed
+        RKey::BackSlash => DKey::BackSlash,
+        RKey::LeftBracket => DKey::LeftBracket,
+        RKey::RightBracket => DKey::RightBracket,
+        RKey::BackQuote => DKey::Grave,
+        RKey::Tab => DKey::Tab,
+        RKey::F1 => DKey::F1,
+        RKey::F2 => DKey::F2,
+        RKey::F3 => DKey::F3,
+        RKey::F4 => DKey::F4,
+        RKey::F5 => DKey::F5,
+        RKey::F6 => DKey::F6,
+        RKey::F7 => DKey::F7,
+        RKey::F8 => DKey::F8,
+        RKey::F9 => DKey::F9,
+        RKey::F10 => DKey::F10,
+        RKey::F11 => DKey::F11,
+        RKey::F12 => DKey::F12,
         _ => return None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_layout::{BANKS, bank_size};
+    use crate::test_support::{TempDir, song};
+
+    #[test]
+    fn large_songs_are_shared_not_reparsed() {
+        // The first groups map to no key, so the note lane below has nothing to show
+        let notes: Vec<(u64, usize)> = (0..20_000).map(|i| (i as u64 * 50, i % 15)).collect();
+        let mut big = song(&notes);
+        for note in &mut big.song_notes[..LANE_CHORDS] {
+            note.key = "1Key99".to_string();
+        }
+        let dir = TempDir::new("large_song");
+        let path = dir.path().join("song.json");
+        std::fs::write(&path, serde_json::to_string(&[&big]).unwrap()).unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let state_arc = Arc::new(Mutex::new(AppState::default()));
+
+        // Only the first request parses the file, so breaking it afterwards goes unnoticed
+        let songs = cached_songs(&state_arc, &path).unwrap();
+        std::fs::write(&path, "not a song").unwrap();
+        assert!(Arc::ptr_eq(
+            &songs,
+            &cached_songs(&state_arc, &path).unwrap()
+        ));
+        assert_eq!(songs[0].song_notes.len(), 20_000);
+
+        // A manual step plays from the shared songs and keeps no copy of its own
+        {
+            let mut state = state_arc.lock().unwrap();
+            state.song_path = Some(path.clone());
+            state.manual_mode = true;
+            state.is_playing = true;
+            // Logs the step instead of opening a keyboard connection
+            state.preview_mode = true;
+        }
+        play_song_manual_tick(Arc::clone(&state_arc), true);
+        {
+            let state = state_arc.lock().unwrap();
+            assert_eq!(state.manual_index, 1);
+            assert_eq!(state.status, "Manual: 1/20000 notes");
+            assert!(Arc::ptr_eq(&songs, &state.songs));
+        }
+        assert_eq!(Arc::strong_count(&songs), 2);
+
+        // The per-frame note lane stops after LANE_CHORDS groups instead of scanning ahead
+        let mapping = note_mapping(&AppState::default());
+        assert!(lane_chords(&songs[0].song_notes, 0, &mapping).is_empty());
+        let chords = lane_chords(&songs[0].song_notes, LANE_CHORDS, &mapping);
+        assert_eq!(chords.len(), LANE_CHORDS - 1);
+        assert_eq!(chords[0], (false, vec![key_name(mapping.keys[10])]));
+    }
+
+    #[test]
+    fn whitespace_padded_sheets_play_in_both_modes() {
+        let json = serde_json::to_string(&[song(&[(0, 0), (250, 4), (500, 7)])]).unwrap();
+        let dir = TempDir::new("padded_sheet");
+        let path = dir.path().join("song.json").to_string_lossy().into_owned();
+        std::fs::write(&path, format!("\n\n  {}  \n\t\n", json)).unwrap();
+
+        assert_eq!(load_song_from_path(&path).unwrap()[0].song_notes.len(), 3);
+        // play_song_gui and play_song_manual_tick both fetch their songs this way
+        let state_arc = Arc::new(Mutex::new(AppState::default()));
+        assert_eq!(
+            cached_songs(&state_arc, &path).unwrap()[0].song_notes.len(),
+            3
+        );
+        // Whitespace isn't stray text worth a warning
+        assert!(load_songs(&path, true).unwrap().warnings.is_empty());
+    }
+
+    #[test]
+    fn panic_key_wins_over_a_binding_being_captured() {
+        let state_arc = Arc::new(Mutex::new(AppState::default()));
+        {
+            let mut state = state_arc.lock().unwrap();
+            state.is_playing = true;
+            begin_hotkey_capture(&mut state, HotkeyCapture::WaitingForStop, "Stop");
+        }
+        let mapping = handle_hotkey_press(&state_arc, Hotkeys::default().panic).unwrap();
+
+        // Playback stops and the key doesn't become the new Stop binding
+        let state = state_arc.lock().unwrap();
+        assert!(!state.is_playing);
+        assert_eq!(state.status, "Panic: released all keys and stopped");
+        assert_eq!(state.hotkey_capture, HotkeyCapture::None);
+        assert!(state.pending_hotkey.is_none());
+        assert_eq!(state.hotkeys.stop, Hotkeys::default().stop);
+        assert_eq!(state.hotkeys.panic, Hotkeys::default().panic);
+
+        // Every note key in every bank comes up, then the modifiers
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&lines);
+        let mut output = LoggingOutput::new(move |line| log.lock().unwrap().push(line));
+        release_all_keys(&mut output, &mapping);
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), note_outputs(&mapping).count() + 3);
+        assert!(lines.iter().all(|line| line.contains("release")));
+        assert!(lines[0].ends_with(" y"));
+        assert!(lines[GRID_SIZE].ends_with(" q"));
+        assert!(lines[lines.len() - 1].ends_with(" Alt"));
+    }
+
+    #[test]
+    fn extra_bank_keys_count_as_note_outputs() {
+        let mapping = note_mapping(&AppState::default());
+        assert!(is_note_output_key(Keycode::Y, &mapping));
+        // "2Key0" plays on q and "3Key0" on 1
+        assert!(is_note_output_key(Keycode::Q, &mapping));
+        assert!(is_note_output_key(Keycode::Key1, &mapping));
+        assert!(!is_note_output_key(Keycode::Space, &mapping));
+        assert_eq!(
+            note_outputs(&mapping).count(),
+            (0..BANKS).map(bank_size).sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn other_layouts_skip_the_extra_banks() {
+        let mut state = AppState {
+            key_layout: KeyLayout::Azerty,
+            ..AppState::default()
+        };
+        let mapping = note_mapping(&state);
+        // AZERTY types symbols on the unshifted digit keys, so bank 3 can't use them
+        assert_eq!(map_key("2Key0", &mapping), None);
+        assert_eq!(map_key("3Key0", &mapping), None);
+        assert!(!is_note_output_key(Keycode::Key1, &mapping));
+        assert_eq!(note_outputs(&mapping).count(), GRID_SIZE);
+
+        let mut banked = song(&[(0, 0), (250, 1)]);
+        banked.song_notes[1].key = "2Key1".to_string();
+        let loaded = LoadedSongs {
+            songs: vec![banked],
+            ..LoadedSongs::default()
+        };
+        apply_loaded_songs(&mut state, "banked.json", Ok(loaded));
+        assert!(
+            state.status.ends_with(
+                "(1 notes are in banks 2 and 3, which the AZERTY layout has no keys for)"
+            )
+        );
+    }
+}
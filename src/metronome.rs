@@ -0,0 +1,116 @@
+use rodio::source::{SineWave, Source};
+use rodio::{OutputStream, OutputStreamHandle};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::AppState;
+use crate::player::playable_speed;
+
+const CLICK_LENGTH: Duration = Duration::from_millis(30);
+const ACCENT_HZ: f32 = 1760.0;
+const SUBDIVISION_HZ: f32 = 1320.0;
+// Rising two-note chime for the end of a song
+const CHIME: [(f32, Duration); 2] = [
+    (880.0, Duration::from_millis(140)),
+    (1320.0, Duration::from_millis(260)),
+];
+
+// Ticks at the active song's bpm while playback is running. The audio device is
+// opened the first time the metronome is switched on.
+pub fn spawn(state_arc: Arc<Mutex<AppState>>, shutdown: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        let mut output: Option<(OutputStream, OutputStreamHandle)> = None;
+        // Next click and its position within the beat; None while silent
+        let mut next_click: Option<(Instant, u32)> = None;
+
+        while !shutdown.load(Ordering::Relaxed) {
+            let timing = {
+                let mut state = state_arc.lock().unwrap();
+                let bpm = state
+                    .songs
+                    .get(state.active_song)
+                    .map_or(0, |song| song.bpm);
+                let active = if state.manual_mode {
+                    state.manual_armed
+                } else {
+                    state.is_playing && !state.is_paused
+                };
+                let running = state.metronome_enabled && active && bpm > 0;
+                if !running {
+                    None
+                } else if output.is_none() {
+                    match OutputStream::try_default() {
+                        Ok(stream) => {
+                            output = Some(stream);
+                            None
+                        }
+                        Err(e) => {
+                            state.metronome_enabled = false;
+                            state.status = format!("Failed to open audio output: {}", e);
+                            None
+                        }
+                    }
+                } else {
+                    // Manual mode follows the player's taps, so only automatic playback scales
+                    let speed = if state.manual_mode {
+                        1.0
+                    } else {
+                        playable_speed(state.speed)
+                    };
+                    let subdivision = state.metronome_subdivision.max(1);
+                    let beat = 60.0 / (bpm as f32 * speed);
+                    Some((
+                        Duration::from_secs_f32(beat / subdivision as f32),
+                        subdivision,
+                    ))
+                }
+            };
+
+            let (Some((interval, subdivision)), Some((_, handle))) = (timing, &output) else {
+                // Pausing or stopping restarts the count on the next beat
+                next_click = None;
+                thread::sleep(Duration::from_millis(20));
+                continue;
+            };
+
+            let (due, tick) = *next_click.get_or_insert((Instant::now(), 0));
+            let now = Instant::now();
+            if now < due {
+                thread::sleep((due - now).min(Duration::from_millis(20)));
+                continue;
+            }
+
+            let frequency = if tick == 0 { ACCENT_HZ } else { SUBDIVISION_HZ };
+            let click = SineWave::new(frequency)
+                .take_duration(CLICK_LENGTH)
+                .amplify(if tick == 0 { 0.3 } else { 0.15 });
+            if let Err(e) = handle.play_raw(click) {
+                crate::debug_log::log(format!("Metronome click failed: {}", e));
+            }
+            next_click = Some((due + interval, (tick + 1) % subdivision));
+        }
+    });
+}
+
+// Plays the end-of-song chime on its own thread, opening the audio device just for it
+pub fn play_chime() {
+    thread::spawn(|| {
+        let (_stream, handle) = match OutputStream::try_default() {
+            Ok(output) => output,
+            Err(e) => {
+                crate::debug_log::log(format!("Chime failed: {}", e));
+                return;
+            }
+        };
+        for (frequency, length) in CHIME {
+            let tone = SineWave::new(frequency).take_duration(length).amplify(0.25);
+            if let Err(e) = handle.play_raw(tone) {
+                crate::debug_log::log(format!("Chime failed: {}", e));
+                return;
+            }
+            thread::sleep(length);
+        }
+    });
+}
@@ -0,0 +1,221 @@
+use midly::num::{u4, u7, u15, u24, u28};
+use midly::{Format, Header, MetaMessage, MidiMessage, Smf, Timing, TrackEvent, TrackEventKind};
+use std::path::Path;
+
+use crate::key_layout::{GRID_SIZE, grid_index, unmap_key};
+use crate::sheet_format::SheetFormat;
+use crate::song_file::LoadedSongs;
+use crate::{Note, Song};
+
+// Semitones above the lowest key for each grid position: two octaves of C major plus the top C
+pub const GRID_SEMITONES: [u8; GRID_SIZE] = [0, 2, 4, 5, 7, 9, 11, 12, 14, 16, 17, 19, 21, 23, 24];
+
+// General MIDI reserves channel 10 for percussion, which has no pitch to place
+const DRUM_CHANNEL: u8 = 9;
+const DEFAULT_TEMPO: u32 = 500_000; // microseconds per quarter note (120 bpm)
+// Exported files put the lowest grid key on middle C
+const EXPORT_BASE_KEY: i32 = 60;
+const EXPORT_TICKS_PER_BEAT: u16 = 480;
+
+pub fn read_midi_file(path: &str) -> Result<LoadedSongs, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let smf = Smf::parse(&bytes).map_err(|e| format!("Failed to parse MIDI file: {}", e))?;
+    let name = Path::new(path)
+        .file_stem()
+        .map_or("Imported".to_string(), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+
+    // Note starts and tempo changes from every track, in absolute ticks
+    let mut notes: Vec<(u64, u8, u8)> = Vec::new();
+    let mut tempos: Vec<(u64, u32)> = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += u64::from(event.delta.as_int());
+            match event.kind {
+                TrackEventKind::Midi {
+                    channel,
+                    message: MidiMessage::NoteOn { key, vel },
+                } if vel > 0 && channel.as_int() != DRUM_CHANNEL => {
+                    notes.push((tick, key.as_int(), vel.as_int()));
+                }
+                TrackEventKind::Meta(MetaMessage::Tempo(tempo)) => {
+                    tempos.push((tick, tempo.as_int()));
+                }
+                _ => {}
+            }
+        }
+    }
+    if notes.is_empty() {
+        return Err("MIDI file has no playable notes".to_string());
+    }
+    notes.sort();
+    tempos.sort();
+
+    let to_ms = tick_converter(smf.header.timing, &tempos);
+    let (base, folded, dropped) = fit_to_grid(notes.iter().map(|&(_, key, _)| key));
+
+    let mut song_notes = Vec::with_capacity(notes.len());
+    for &(tick, key, velocity) in &notes {
+        if let Some(key) = grid_position(key, base).and_then(unmap_key) {
+            song_notes.push(Note {
+                key,
+                time: to_ms(tick),
+                duration: None,
+                velocity: Some(velocity),
+            });
+        }
+    }
+    song_notes.dedup_by(|a, b| a.key == b.key && a.time == b.time);
+
+    let first_tempo = tempos.first().map_or(DEFAULT_TEMPO, |&(_, tempo)| tempo);
+    let mut warnings = Vec::new();
+    if folded > 0 {
+        warnings.push(format!(
+            "{} notes moved by an octave to fit the range",
+            folded
+        ));
+    }
+    if dropped > 0 {
+        warnings.push(format!("{} sharp/flat notes dropped", dropped));
+    }
+
+    Ok(LoadedSongs {
+        songs: vec![Song {
+            name,
+            bpm: (60_000_000 / first_tempo.max(1)).max(1),
+            bits_per_page: 16,
+            pitch_level: 0,
+            help_text: String::new(),
+            song_notes,
+        }],
+        warnings,
+        format: SheetFormat::Midi,
+    })
+}
+
+// Writes one song as a single-track MIDI file; chords become simultaneous note-ons
+pub fn write_midi_file(path: &str, song: &Song) -> Result<(), String> {
+    let bpm = u64::from(song.bpm.max(1));
+    let to_ticks = |ms: u64| ms * bpm * u64::from(EXPORT_TICKS_PER_BEAT) / 60_000;
+    // Notes without a duration sound for an eighth note
+    let note_length = u64::from(EXPORT_TICKS_PER_BEAT / 2);
+
+    // (tick, is_note_on, key, velocity); offs sort before ons so repeated keys retrigger cleanly
+    let mut events: Vec<(u64, bool, u8, u8)> = Vec::new();
+    for note in &song.song_notes {
+        let Some(index) = grid_index(&note.key) else {
+            continue;
+        };
+        let key = EXPORT_BASE_KEY + song.pitch_level + i32::from(GRID_SEMITONES[index]);
+        let Ok(key) = u8::try_from(key.clamp(0, 127)) else {
+            continue;
+        };
+        let start = to_ticks(note.time);
+        let length = note.duration.map_or(note_length, |ms| to_ticks(ms).max(1));
+        let velocity = note.velocity.unwrap_or(100).clamp(1, 127);
+        events.push((start, true, key, velocity));
+        events.push((start + length, false, key, 0));
+    }
+    events.sort();
+    events.dedup();
+
+    let tempo = (60_000_000 / bpm) as u32;
+    let mut track = vec![TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::Tempo(u24::new(tempo))),
+    }];
+    let mut last_tick = 0;
+    for (tick, is_note_on, key, velocity) in events {
+        let message = if is_note_on {
+            MidiMessage::NoteOn {
+                key: u7::new(key),
+                vel: u7::new(velocity),
+            }
+        } else {
+            MidiMessage::NoteOff {
+                key: u7::new(key),
+                vel: u7::new(0),
+            }
+        };
+        track.push(TrackEvent {
+            delta: u28::new((tick - last_tick) as u32),
+            kind: TrackEventKind::Midi {
+                channel: u4::new(0),
+                message,
+            },
+        });
+        last_tick = tick;
+    }
+    track.push(TrackEvent {
+        delta: u28::new(0),
+        kind: TrackEventKind::Meta(MetaMessage::EndOfTrack),
+    });
+
+    let mut smf = Smf::new(Header::new(
+        Format::SingleTrack,
+        Timing::Metrical(u15::new(EXPORT_TICKS_PER_BEAT)),
+    ));
+    smf.tracks.push(track);
+    smf.save(path)
+        .map_err(|e| format!("Failed to write MIDI file: {}", e))
+}
+
+// Milliseconds for an absolute tick, following the file's tempo changes
+fn tick_converter(timing: Timing, tempos: &[(u64, u32)]) -> impl Fn(u64) -> u64 + '_ {
+    move |tick| match timing {
+        Timing::Timecode(fps, subframes) => {
+            let ticks_per_sec = fps.as_f32() as f64 * f64::from(subframes);
+            (tick as f64 * 1000.0 / ticks_per_sec) as u64
+        }
+        Timing::Metrical(ticks_per_beat) => {
+            let ticks_per_beat = f64::from(ticks_per_beat.as_int().max(1));
+            let mut micros = 0.0;
+            let mut last_tick = 0;
+            let mut tempo = DEFAULT_TEMPO;
+            for &(change_tick, new_tempo) in tempos.iter().take_while(|&&(t, _)| t < tick) {
+                micros += (change_tick - last_tick) as f64 * f64::from(tempo) / ticks_per_beat;
+                last_tick = change_tick;
+                tempo = new_tempo;
+            }
+            micros += (tick - last_tick) as f64 * f64::from(tempo) / ticks_per_beat;
+            (micros / 1000.0) as u64
+        }
+    }
+}
+
+// Picks the lowest grid note (a C) that keeps the most notes in range, then says how
+// many will be folded by octaves and how many can't be placed at all
+fn fit_to_grid(keys: impl Iterator<Item = u8> + Clone) -> (u8, usize, usize) {
+    let base = (0..=10u8)
+        .map(|octave| octave * 12)
+        .max_by_key(|&base| {
+            keys.clone()
+                .filter(|&key| key >= base && GRID_SEMITONES.contains(&(key - base)))
+                .count()
+        })
+        .unwrap_or(60);
+    let mut folded = 0;
+    let mut dropped = 0;
+    for key in keys {
+        match grid_position(key, base) {
+            None => dropped += 1,
+            Some(_) if key < base || key > base + 24 => folded += 1,
+            Some(_) => {}
+        }
+    }
+    (base, folded, dropped)
+}
+
+// Grid index for a MIDI key, moving it by octaves into range when needed
+fn grid_position(key: u8, base: u8) -> Option<usize> {
+    let mut offset = i32::from(key) - i32::from(base);
+    while offset < 0 {
+        offset += 12;
+    }
+    while offset > 24 {
+        offset -= 12;
+    }
+    GRID_SEMITONES.iter().position(|&s| i32::from(s) == offset)
+}
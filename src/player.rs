@@ -0,0 +1,739 @@
+use enigo::Key;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use crate::key_output::KeyOutput;
+use crate::timing::TimingProfile;
+use crate::{MAX_SPEED_LIMIT, MIN_SPEED, NoteMapping, Song, lead_in_ms, map_key};
+
+// How long the final chord rings when holding it is enabled
+const LAST_CHORD_HOLD: Duration = Duration::from_millis(1500);
+// A key sustained into the next chord comes up when that chord is pressed; this only
+// bounds how long it can stay down if playback stalls before then
+const SUSTAIN_LIMIT: Duration = Duration::from_secs(10);
+
+// Settings fixed for one run through a song
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerOptions {
+    pub timing: TimingProfile,
+    pub mapping: NoteMapping,
+    pub trim_lead_in: bool,
+    pub hold_last_chord: bool,
+    // Keys shared by consecutive chords stay down instead of being tapped again
+    pub sustain_shared_keys: bool,
+    pub humanize_seed: Option<u64>,
+    // Shifts the whole song later (positive) or earlier, never before its start
+    pub time_offset_ms: i64,
+}
+
+// Settings that may change while the song plays, passed to every tick
+#[derive(Debug, Clone, Copy)]
+pub struct LiveSettings {
+    pub speed: f32,
+    pub legato: f32,
+    pub repeat_gap: Duration,
+    // Most a press may be nudged early or late
+    pub timing_jitter: Duration,
+    // No hold is shorter than this, even where notes are packed tightly
+    pub min_hold: Duration,
+}
+
+// A chord the player just pressed
+#[derive(Debug)]
+pub struct PlayedChord {
+    pub notes: Range<usize>,
+    pub keys: Vec<Key>,
+    pub hold: Duration,
+    pub late: Duration,
+}
+
+pub enum Step {
+    // Nothing to do yet; tick again by this time
+    Wait(Instant),
+    Played(PlayedChord),
+    // Every note was played and released
+    Finished,
+}
+
+// Plays one song's notes into a key output. It never sleeps or locks anything itself:
+// the caller ticks it and waits as told, so it can be driven by any clock.
+pub struct Player<'a> {
+    song: &'a Song,
+    output: &'a mut dyn KeyOutput,
+    options: PlayerOptions,
+    rng: StdRng,
+    // Distinct note times, and which of them each note belongs to
+    group_times: Vec<u64>,
+    note_groups: Vec<usize>,
+    // Next note to play
+    index: usize,
+    // Each note is scheduled from the previous one's slot by their time delta, so
+    // seeks and resumes only need to move this anchor
+    anchor: Instant,
+    anchor_time: u64,
+    // Keys are released on their own schedule so holds never delay the next press
+    held: Vec<(Instant, Key)>,
+    // When each key last came (or will come) up, to space out repeated presses
+    released_at: Vec<(Key, Instant)>,
+    // Random onset offset in ms rolled for the note at this index
+    jitter: Option<(usize, i64)>,
+}
+
+impl<'a> Player<'a> {
+    pub fn new(song: &'a Song, output: &'a mut dyn KeyOutput, options: PlayerOptions) -> Self {
+        // A fixed seed makes the variation repeatable
+        let rng = match options.humanize_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        };
+        let (group_times, note_groups) = chord_groups(song);
+        let mut player = Self {
+            song,
+            output,
+            options,
+            rng,
+            group_times,
+            note_groups,
+            index: 0,
+            anchor: Instant::now(),
+            anchor_time: 0,
+            held: Vec::new(),
+            released_at: Vec::new(),
+            jitter: None,
+        };
+        player.stop();
+        player
+    }
+
+    // Index of the next note to play
+    pub fn position(&self) -> usize {
+        self.index
+    }
+
+    // Starts or resumes from the current position, with its note due right away
+    // rather than rushing to catch up
+    pub fn play(&mut self, now: Instant) {
+        self.anchor = now;
+        if let Some(note) = self.song.song_notes.get(self.index) {
+            // Trimming the silent intro starts the anchor at the first note so it plays
+            // immediately; otherwise a fresh start waits out the sheet's lead-in
+            if self.index > 0 || self.options.trim_lead_in {
+                self.anchor_time = note.time;
+            }
+            // Since every note is timed from the one before, shifting the song only
+            // changes the wait for the first one
+            if self.index == 0 {
+                let offset = self.options.time_offset_ms;
+                if offset >= 0 {
+                    self.anchor += Duration::from_millis(offset as u64);
+                } else {
+                    self.anchor_time = (self.anchor_time + offset.unsigned_abs()).min(note.time);
+                }
+            }
+        }
+        // Everything came up when we paused, long enough ago to press again
+        self.released_at.clear();
+    }
+
+    // Lets go of every key; play() carries on from the same note
+    pub fn pause(&mut self) {
+        self.release_held(None);
+    }
+
+    // Lets go of every key and goes back to the first note
+    pub fn stop(&mut self) {
+        self.release_held(None);
+        self.index = 0;
+        self.anchor_time = if self.options.trim_lead_in {
+            lead_in_ms(self.song)
+        } else {
+            0
+        };
+    }
+
+    // Jumps to a note, which then plays immediately
+    pub fn seek(&mut self, index: usize, now: Instant) {
+        let Some(last) = self.song.song_notes.len().checked_sub(1) else {
+            return;
+        };
+        self.index = index.min(last);
+        self.anchor_time = self.song.song_notes[self.index].time;
+        self.anchor = now;
+    }
+
+    // Goes back to an earlier note without breaking the rhythm: it comes `gap_ms`
+    // (at 1x) after the chord just played
+    pub fn jump_back(&mut self, index: usize, gap_ms: u64) {
+        let Some(last) = self.song.song_notes.len().checked_sub(1) else {
+            return;
+        };
+        self.index = index.min(last);
+        self.anchor_time = self.song.song_notes[self.index].time.saturating_sub(gap_ms);
+    }
+
+    // Releases due keys and presses the next chord if its time has come
+    pub fn tick(&mut self, now: Instant, settings: &LiveSettings) -> Step {
+        self.release_held(Some(now));
+        let next_release = self.held.iter().map(|&(at, _)| at).min();
+
+        let speed = playable_speed(settings.speed);
+
+        // Chords without a playable key are stepped over here rather than by recursing,
+        // since a mostly out-of-range import can hold thousands of them in a row
+        let (note, due, press_at, group, notes, keys) = loop {
+            let Some(note) = self.song.song_notes.get(self.index) else {
+                return match next_release {
+                    Some(at) => Step::Wait(at),
+                    None => Step::Finished,
+                };
+            };
+            // Timed from the previous note; a note that sits before the anchor
+            // (e.g. right after seeking backward) fires immediately
+            let delta = note.time.saturating_sub(self.anchor_time);
+            let due = self.anchor + Duration::from_millis((delta as f32 / speed) as u64);
+            let press_at = self.jittered(due, delta, settings);
+            if now < press_at {
+                return Step::Wait(next_release.map_or(press_at, |at| at.min(press_at)));
+            }
+
+            // Every note sharing this time is one chord: pressed together, released together
+            let group = self.note_groups[self.index];
+            let notes = self.index..chord_end(self.song, self.index);
+            let keys = chord_keys(self.song, notes.clone(), &self.options.mapping);
+            if keys.is_empty() {
+                self.advance(due, notes.end);
+                continue;
+            }
+            break (note, due, press_at, group, notes, keys);
+        };
+
+        let (base_hold, gap) = chord_articulation(&self.group_times, group, &self.options.timing);
+        // Holds and gaps are tuned for 1x, so they scale with the tempo like the notes do
+        let gap = (gap as f32 / speed) as u64;
+
+        // Keys still down from an earlier chord must come up before they can sound again.
+        // When sustaining, the ones this chord shares stay down and the rest come up.
+        let sustain = self.options.sustain_shared_keys;
+        let mut sustained: Vec<Key> = Vec::new();
+        let output = &mut *self.output;
+        let before = self.held.len();
+        self.held.retain(|&(_, held_key)| {
+            let shared = keys.contains(&held_key);
+            if sustain && shared {
+                sustained.push(held_key);
+                return true;
+            }
+            if shared || sustain {
+                output.release(held_key);
+                return false;
+            }
+            true
+        });
+        if self.held.len() != before {
+            for entry in self
+                .released_at
+                .iter_mut()
+                .filter(|(key, _)| keys.contains(key))
+            {
+                entry.1 = entry.1.min(now);
+            }
+        }
+        // Repeated keys stay up for the articulation gap, and never less than the
+        // repeat gap, which is in real time because it's about what the game registers;
+        // this can push the chord back a little
+        let min_up = Duration::from_millis(gap).max(settings.repeat_gap);
+        let ready = self
+            .released_at
+            .iter()
+            .filter(|(key, _)| keys.contains(key) && !sustained.contains(key))
+            .map(|&(_, up)| up + min_up)
+            .max();
+        if let Some(ready) = ready
+            && ready > now
+        {
+            return Step::Wait(ready);
+        }
+
+        // A duration from the sheet wins over the heuristic; the longest in a chord is used
+        let sheet_duration = self.song.song_notes[notes.clone()]
+            .iter()
+            .filter_map(|chord_note| chord_note.duration)
+            .max();
+        let mut hold = if let Some(duration) = sheet_duration {
+            Duration::from_millis((duration as f32 / speed) as u64)
+        } else {
+            // Add a small variation to hold duration for a more natural sound
+            let spread = self.options.timing.variation_ms as i64;
+            let variation = self.rng.random_range(-spread..=spread);
+            let hold = (base_hold as i64 + variation).max(0) as f32 / speed;
+            Duration::from_millis(hold as u64)
+        };
+
+        let next_keys = chord_keys(
+            self.song,
+            notes.end..chord_end(self.song, notes.end),
+            &self.options.mapping,
+        );
+        // Keep the hold and gap inside the time before the next chord; legato
+        // stretches the hold across that time
+        if let Some(&next_time) = self.group_times.get(group + 1)
+            && sheet_duration.is_none()
+        {
+            let until_next = ((next_time - note.time) as f32 / speed) as u64;
+            let legato_hold = Duration::from_millis((until_next as f32 * settings.legato) as u64);
+            hold = hold.max(legato_hold);
+            // A key the next chord presses again needs room for its repeat gap, unless
+            // it's sustained through
+            let gap = if !sustain && next_keys.iter().any(|key| keys.contains(key)) {
+                gap.max(settings.repeat_gap.as_millis() as u64)
+            } else {
+                gap
+            };
+            let budget = Duration::from_millis(until_next.saturating_sub(gap));
+            hold = hold.min(budget);
+        } else if notes.end == self.song.song_notes.len() && self.options.hold_last_chord {
+            // Let the final chord ring for a while
+            hold = hold.max(LAST_CHORD_HOLD);
+        }
+        // Games can miss presses shorter than this; it wins over the budget above, so
+        // the gap before the next chord shrinks instead
+        hold = hold.max(settings.min_hold);
+
+        for &key in keys.iter().filter(|key| !sustained.contains(key)) {
+            self.output.press(key);
+        }
+        self.held.retain(|(_, key)| !sustained.contains(key));
+        self.released_at.retain(|(key, _)| !keys.contains(key));
+        for &key in &keys {
+            let release_at = if sustain && next_keys.contains(&key) {
+                now + SUSTAIN_LIMIT
+            } else {
+                now + hold
+            };
+            self.held.push((release_at, key));
+            self.released_at.push((key, release_at));
+        }
+        self.advance(due, notes.end);
+
+        Step::Played(PlayedChord {
+            notes,
+            keys,
+            hold,
+            late: now.saturating_duration_since(press_at),
+        })
+    }
+
+    // When to actually press the next chord: `due` plus a random offset, rolled once
+    // per chord and kept under half the gap to either neighbour so chords never swap.
+    // The next chord is still timed from `due`, so the offsets don't add up.
+    fn jittered(&mut self, due: Instant, delta: u64, settings: &LiveSettings) -> Instant {
+        let limit = settings.timing_jitter.as_millis() as i64;
+        if limit == 0 {
+            return due;
+        }
+        if self.jitter.is_none_or(|(index, _)| index != self.index) {
+            let group = self.note_groups[self.index];
+            let after = self
+                .group_times
+                .get(group + 1)
+                .map_or(u64::MAX, |&next| next - self.group_times[group]);
+            let room = (delta.min(after) as f32 / playable_speed(settings.speed) / 2.0) as i64;
+            let spread = limit.min(room);
+            self.jitter = Some((self.index, self.rng.random_range(-spread..=spread)));
+        }
+        let offset = self.jitter.map_or(0, |(_, offset)| offset);
+        let shift = Duration::from_millis(offset.unsigned_abs());
+        if offset < 0 {
+            due.checked_sub(shift).unwrap_or(due)
+        } else {
+            due + shift
+        }
+    }
+
+    // Moves past a chord, anchoring the next one to this chord's slot
+    fn advance(&mut self, due: Instant, next: usize) {
+        self.anchor = due;
+        self.anchor_time = self.song.song_notes[self.index].time;
+        self.index = next;
+    }
+
+    // Releases held keys that are due by `now`, or all of them when `now` is None
+    fn release_held(&mut self, now: Option<Instant>) {
+        let output = &mut *self.output;
+        self.held.retain(|&(at, key)| {
+            if now.is_some_and(|now| at > now) {
+                return true;
+            }
+            output.release(key);
+            false
+        });
+    }
+}
+
+// A speed that's safe to divide note times by, whatever path it came in on: zero,
+// negative and NaN speeds play at 1x, and the rest are kept to the supported range
+pub fn playable_speed(speed: f32) -> f32 {
+    if speed.is_finite() && speed > 0.0 {
+        speed.clamp(MIN_SPEED, MAX_SPEED_LIMIT)
+    } else {
+        1.0
+    }
+}
+
+// One past the last note of the chord starting at `start`
+pub fn chord_end(song: &Song, start: usize) -> usize {
+    let notes = &song.song_notes;
+    let Some(time) = notes.get(start).map(|note| note.time) else {
+        return start;
+    };
+    start
+        + notes[start..]
+            .iter()
+            .take_while(|note| note.time == time)
+            .count()
+}
+
+// First note of the chord that ends right before `end`
+pub fn chord_start(song: &Song, end: usize) -> usize {
+    let notes = &song.song_notes[..end];
+    let Some(time) = notes.last().map(|note| note.time) else {
+        return end;
+    };
+    end - notes
+        .iter()
+        .rev()
+        .take_while(|note| note.time == time)
+        .count()
+}
+
+// Distinct keys to press for a range of notes
+pub fn chord_keys(song: &Song, notes: Range<usize>, mapping: &NoteMapping) -> Vec<Key> {
+    let mut keys: Vec<Key> = Vec::new();
+    for note in &song.song_notes[notes] {
+        if let Some(key) = map_key(&note.key, mapping)
+            && !keys.contains(&key)
+        {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+// Distinct note times, plus which of those groups each note belongs to
+fn chord_groups(song: &Song) -> (Vec<u64>, Vec<usize>) {
+    let mut group_times: Vec<u64> = Vec::new();
+    let mut note_groups = Vec::with_capacity(song.song_notes.len());
+    for note in &song.song_notes {
+        if group_times.last() != Some(&note.time) {
+            group_times.push(note.time);
+        }
+        note_groups.push(group_times.len() - 1);
+    }
+    (group_times, note_groups)
+}
+
+// Base hold and trailing gap (ms) for every note in a chord group
+fn chord_articulation(group_times: &[u64], group: usize, timing: &TimingProfile) -> (u64, u64) {
+    let is_important = group.is_multiple_of(4);
+    let is_melodic_peak = group > 0
+        && group < group_times.len() - 1
+        && group_times[group] > group_times[group - 1]
+        && group_times[group] > group_times[group + 1];
+
+    // Set note duration based on importance
+    let base_hold = if is_important {
+        timing.hold_important_ms
+    } else if is_melodic_peak {
+        timing.hold_peak_ms
+    } else {
+        timing.hold_normal_ms
+    };
+    let gap = if is_important {
+        timing.gap_important_ms
+    } else {
+        timing.gap_normal_ms
+    };
+    (base_hold, gap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_layout::KeyLayout;
+    use crate::test_support::song;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Records every key event at the time shown on the test's virtual clock
+    struct RecordingOutput {
+        clock: Rc<Cell<Instant>>,
+        events: Vec<(Instant, bool, Key)>,
+    }
+
+    impl RecordingOutput {
+        fn new(clock: &Rc<Cell<Instant>>) -> Self {
+            Self {
+                clock: Rc::clone(clock),
+                events: Vec::new(),
+            }
+        }
+
+        // Milliseconds after `start` of each press (or release) of `key`
+        fn times(&self, start: Instant, pressed: bool, key: Key) -> Vec<u64> {
+            self.events
+                .iter()
+                .filter(|&&(_, is_press, event_key)| is_press == pressed && event_key == key)
+                .map(|&(at, _, _)| at.duration_since(start).as_millis() as u64)
+                .collect()
+        }
+    }
+
+    impl KeyOutput for RecordingOutput {
+        fn press(&mut self, key: Key) {
+            self.events.push((self.clock.get(), true, key));
+        }
+
+        fn release(&mut self, key: Key) {
+            self.events.push((self.clock.get(), false, key));
+        }
+    }
+
+    fn key(index: usize) -> Key {
+        KeyLayout::default().note_keys()[index]
+    }
+
+    fn options() -> PlayerOptions {
+        PlayerOptions {
+            timing: TimingProfile::default(),
+            mapping: NoteMapping {
+                keys: KeyLayout::default().note_keys(),
+                transpose: 0,
+                layout: KeyLayout::default(),
+            },
+            trim_lead_in: false,
+            hold_last_chord: false,
+            sustain_shared_keys: false,
+            humanize_seed: Some(7),
+            time_offset_ms: 0,
+        }
+    }
+
+    fn settings() -> LiveSettings {
+        LiveSettings {
+            speed: 1.0,
+            legato: 0.0,
+            repeat_gap: Duration::from_millis(20),
+            timing_jitter: Duration::ZERO,
+            min_hold: Duration::ZERO,
+        }
+    }
+
+    // Ticks on a virtual clock that jumps straight to each wait, until `done` says so
+    // or the song ends
+    fn run_until(
+        player: &mut Player,
+        clock: &Cell<Instant>,
+        settings: &LiveSettings,
+        mut done: impl FnMut(&Player) -> bool,
+    ) {
+        for _ in 0..10_000 {
+            if done(player) {
+                return;
+            }
+            match player.tick(clock.get(), settings) {
+                Step::Wait(at) => clock.set(at.max(clock.get())),
+                Step::Played(_) => {}
+                Step::Finished => return,
+            }
+        }
+        panic!("playback never finished");
+    }
+
+    // Six notes half a second apart, each on its own key
+    fn scale() -> Song {
+        song(&[(0, 0), (500, 1), (1000, 2), (1500, 3), (2000, 4), (2500, 5)])
+    }
+
+    #[test]
+    fn chord_members_share_hold_and_gap() {
+        // An accented chord, a plain one and a melodic peak, three notes each
+        let song = song(&[
+            (0, 0),
+            (0, 4),
+            (0, 7),
+            (400, 1),
+            (400, 5),
+            (400, 8),
+            (900, 2),
+            (900, 6),
+            (900, 9),
+            (1200, 3),
+        ]);
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let start = clock.get();
+        let mut output = RecordingOutput::new(&clock);
+        {
+            let mut player = Player::new(&song, &mut output, options());
+            player.play(start);
+            run_until(&mut player, &clock, &settings(), |_| false);
+        }
+        for chord in [[0, 4, 7], [1, 5, 8], [2, 6, 9]] {
+            let presses = output.times(start, true, key(chord[0]));
+            let releases = output.times(start, false, key(chord[0]));
+            for index in chord {
+                assert_eq!(output.times(start, true, key(index)), presses);
+                assert_eq!(output.times(start, false, key(index)), releases);
+            }
+        }
+    }
+
+    #[test]
+    fn seeking_forward_rebases_later_presses() {
+        let song = scale();
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let start = clock.get();
+        let mut output = RecordingOutput::new(&clock);
+        {
+            let mut player = Player::new(&song, &mut output, options());
+            player.play(start);
+            run_until(&mut player, &clock, &settings(), |player| {
+                player.position() == 2
+            });
+            clock.set(start + Duration::from_millis(700));
+            player.seek(4, clock.get());
+            run_until(&mut player, &clock, &settings(), |_| false);
+        }
+        assert_eq!(output.times(start, true, key(1)), [500]);
+        // The skipped notes never play; the target plays at once and the rest follow
+        // on the sheet's spacing from there
+        assert!(output.times(start, true, key(2)).is_empty());
+        assert!(output.times(start, true, key(3)).is_empty());
+        assert_eq!(output.times(start, true, key(4)), [700]);
+        assert_eq!(output.times(start, true, key(5)), [1200]);
+    }
+
+    #[test]
+    fn seeking_backward_rebases_later_presses() {
+        let song = scale();
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let start = clock.get();
+        let mut output = RecordingOutput::new(&clock);
+        {
+            let mut player = Player::new(&song, &mut output, options());
+            player.play(start);
+            run_until(&mut player, &clock, &settings(), |player| {
+                player.position() == 4
+            });
+            clock.set(start + Duration::from_millis(1600));
+            player.seek(1, clock.get());
+            run_until(&mut player, &clock, &settings(), |_| false);
+        }
+        assert_eq!(output.times(start, true, key(0)), [0]);
+        assert_eq!(output.times(start, true, key(1)), [500, 1600]);
+        assert_eq!(output.times(start, true, key(2)), [1000, 2100]);
+        assert_eq!(output.times(start, true, key(3)), [1500, 2600]);
+        assert_eq!(output.times(start, true, key(5)), [3600]);
+    }
+
+    #[test]
+    fn overlapping_holds_do_not_delay_presses() {
+        // A long fast run where every key would still be held when the next one is due
+        let notes: Vec<(u64, usize)> = (0..200).map(|i| (i * 40, i as usize % 15)).collect();
+        let song = song(&notes);
+        let settings = LiveSettings {
+            legato: 1.0,
+            min_hold: Duration::from_millis(100),
+            ..settings()
+        };
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let start = clock.get();
+        let mut output = RecordingOutput::new(&clock);
+        {
+            let mut player = Player::new(&song, &mut output, options());
+            player.play(start);
+            run_until(&mut player, &clock, &settings, |_| false);
+        }
+        let presses: Vec<u64> = output
+            .events
+            .iter()
+            .filter(|&&(_, pressed, _)| pressed)
+            .map(|&(at, _, _)| at.duration_since(start).as_millis() as u64)
+            .collect();
+        assert_eq!(presses.len(), notes.len());
+        for (&pressed, &(target, _)) in presses.iter().zip(&notes) {
+            assert!(
+                pressed.abs_diff(target) <= 2,
+                "pressed at {pressed} ms, due at {target} ms"
+            );
+        }
+    }
+
+    #[test]
+    fn any_speed_gives_a_bounded_delay() {
+        let song = song(&[(0, 0), (1000, 1)]);
+        let speeds = [
+            0.0,
+            -0.0,
+            -1.0,
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            1e30,
+        ];
+        let shortest = (1000.0 / MAX_SPEED_LIMIT) as u64;
+        let longest = (1000.0 / MIN_SPEED) as u64;
+        for speed in speeds {
+            let delay = 1000.0 / playable_speed(speed);
+            assert!(delay.is_finite(), "speed {speed} gave a {delay} ms delay");
+
+            let clock = Rc::new(Cell::new(Instant::now()));
+            let start = clock.get();
+            let mut output = RecordingOutput::new(&clock);
+            {
+                let mut player = Player::new(&song, &mut output, options());
+                player.play(start);
+                let settings = LiveSettings {
+                    speed,
+                    ..settings()
+                };
+                run_until(&mut player, &clock, &settings, |_| false);
+            }
+            let pressed = output.times(start, true, key(1));
+            assert_eq!(pressed.len(), 1, "speed {speed}");
+            assert!(
+                (shortest..=longest).contains(&pressed[0]),
+                "speed {speed} pressed the second note at {} ms",
+                pressed[0]
+            );
+        }
+    }
+
+    #[test]
+    fn long_unmapped_runs_are_skipped_in_one_tick() {
+        // Enough keyless chords in a row to overflow the stack if each took a call
+        let mut notes: Vec<(u64, usize)> = (0..200_000).map(|i| (i * 10, 0)).collect();
+        notes.push((2_000_000, 0));
+        let mut song = song(&notes);
+        for note in &mut song.song_notes[..200_000] {
+            note.key = "1Key99".to_string();
+        }
+        let clock = Rc::new(Cell::new(Instant::now()));
+        let start = clock.get();
+        let mut output = RecordingOutput::new(&clock);
+        {
+            let mut player = Player::new(&song, &mut output, options());
+            player.play(start);
+            // A lag spike leaves the whole run overdue at once
+            clock.set(start + Duration::from_millis(2_500_000));
+            assert!(matches!(
+                player.tick(clock.get(), &settings()),
+                Step::Played(_)
+            ));
+        }
+        assert_eq!(output.times(start, true, key(0)), [2_500_000]);
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Instant;
+
+use crate::player::playable_speed;
+
+// Taps within this many ms of the sheet's timing count as on time
+const ON_TIME_MS: i64 = 60;
+
+// Scores manual-mode taps against the gaps written in the sheet
+#[derive(Debug, Default)]
+pub struct TapAccuracy {
+    // Sheet time of the last scored chord and when it was tapped
+    last_tap: Option<(u64, Instant)>,
+    // Note index and how late each tap was in ms; negative is early
+    deviations: Vec<(usize, i64)>,
+}
+
+impl TapAccuracy {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    // The next tap has nothing to be measured against, e.g. after a seek or pause
+    pub fn restart(&mut self) {
+        self.last_tap = None;
+    }
+
+    // Compares the time since the previous tap with the sheet's gap at this speed
+    pub fn record(
+        &mut self,
+        index: usize,
+        note_time: u64,
+        tapped_at: Instant,
+        speed: f32,
+    ) -> Option<i64> {
+        let previous = self.last_tap.replace((note_time, tapped_at));
+        let (previous_time, previous_tap) = previous?;
+        if note_time <= previous_time {
+            return None;
+        }
+        let expected = ((note_time - previous_time) as f32 / playable_speed(speed)) as i64;
+        let actual = tapped_at.duration_since(previous_tap).as_millis() as i64;
+        let deviation = actual - expected;
+        self.deviations.push((index, deviation));
+        Some(deviation)
+    }
+
+    pub fn last(&self) -> Option<i64> {
+        self.deviations.last().map(|&(_, deviation)| deviation)
+    }
+
+    // How many taps were early, on time and late
+    pub fn counts(&self) -> (usize, usize, usize) {
+        let mut counts = (0, 0, 0);
+        for &(_, deviation) in &self.deviations {
+            if deviation < -ON_TIME_MS {
+                counts.0 += 1;
+            } else if deviation > ON_TIME_MS {
+                counts.2 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+        counts
+    }
+
+    pub fn mean_offset_ms(&self) -> Option<i64> {
+        let count = self.deviations.len() as i64;
+        let total: i64 = self
+            .deviations
+            .iter()
+            .map(|&(_, deviation)| deviation.abs())
+            .sum();
+        total.checked_div(count)
+    }
+}
+
+pub fn describe(deviation: i64) -> String {
+    if deviation < -ON_TIME_MS {
+        format!("{} ms early", -deviation)
+    } else if deviation > ON_TIME_MS {
+        format!("{} ms late", deviation)
+    } else {
+        "on time".to_string()
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+
+use serde_json::{Map, Value};
+
+use crate::key_layout::{bank_key, char_to_grid, unmap_key};
+use crate::{Note, Song};
+
+// Defaults for metadata a community sheet leaves out
+const DEFAULT_BPM: u32 = 120;
+const DEFAULT_BITS_PER_PAGE: u32 = 16;
+// Sky Music Nightly names the key instead of storing pitchLevel
+const PITCH_NAMES: [&[&str]; 12] = [
+    &["C"],
+    &["C#", "Db"],
+    &["D"],
+    &["D#", "Eb"],
+    &["E"],
+    &["F"],
+    &["F#", "Gb"],
+    &["G"],
+    &["G#", "Ab"],
+    &["A"],
+    &["A#", "Bb"],
+    &["B"],
+];
+
+// Which schema a file turned out to use, shown in the status bar after loading
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SheetFormat {
+    #[default]
+    SkyStudio,
+    // Sky Studio-style notes with metadata missing, or keys written "Key5" or "y"
+    Community,
+    // Sky Music Nightly recordings: `notes` as [key index, time, layer] arrays
+    NightlyRecorded,
+    Midi,
+}
+
+impl fmt::Display for SheetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::SkyStudio => "Sky Studio sheet",
+            Self::Community => "community sheet",
+            Self::NightlyRecorded => "Sky Music Nightly recording",
+            Self::Midi => "MIDI file",
+        };
+        f.write_str(name)
+    }
+}
+
+// Every field of a Sky Studio song. An object with all of them that still failed to
+// parse is a broken Sky Studio sheet, not another schema, and gets Sky Studio's error.
+const SKY_STUDIO_FIELDS: [&str; 6] = [
+    "name",
+    "bpm",
+    "bitsPerPage",
+    "pitchLevel",
+    "helpText",
+    "songNotes",
+];
+
+// Songs read from another schema, and how many songs or notes were too broken to use
+#[derive(Debug)]
+pub struct CommunitySheet {
+    pub songs: Vec<Song>,
+    pub format: SheetFormat,
+    pub skipped: usize,
+}
+
+// Reads songs from schemas other than Sky Studio's, filling in whatever they leave
+// out. None if the JSON doesn't look like any of them.
+pub fn parse_community(json: &str) -> Option<CommunitySheet> {
+    let value: Value = serde_json::from_str(json).ok()?;
+    let items: Vec<&Value> = match &value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(object) => match object.get("songs").and_then(Value::as_array) {
+            Some(items) => items.iter().collect(),
+            None => vec![&value],
+        },
+        _ => return None,
+    };
+
+    let mut format = SheetFormat::Community;
+    let mut songs = Vec::new();
+    let mut skipped = 0;
+    for item in items {
+        let Some(object) = item.as_object() else {
+            skipped += 1;
+            continue;
+        };
+        let (notes, read_note): (&Vec<Value>, fn(&Value) -> Option<Note>) =
+            if let Some(notes) = object.get("songNotes").and_then(Value::as_array) {
+                (notes, object_note)
+            } else if let Some(notes) = object.get("notes").and_then(Value::as_array) {
+                format = SheetFormat::NightlyRecorded;
+                (notes, recorded_note)
+            } else {
+                skipped += 1;
+                continue;
+            };
+        let song_notes: Vec<Note> = notes.iter().filter_map(read_note).collect();
+        let dropped = notes.len() - song_notes.len();
+
+        let text = |field: &str| object.get(field).and_then(Value::as_str);
+        let number = |field: &str| {
+            object
+                .get(field)
+                .and_then(Value::as_f64)
+                .map(|n| n.max(0.0) as u32)
+        };
+        let pitch_number = object
+            .get("pitchLevel")
+            .and_then(Value::as_i64)
+            .map(|level| level as i32);
+        let native = SKY_STUDIO_FIELDS
+            .iter()
+            .all(|field| object.contains_key(*field));
+        let fields_read = text("name").is_some()
+            && number("bpm").is_some()
+            && number("bitsPerPage").is_some()
+            && pitch_number.is_some()
+            && text("helpText").is_some();
+        if native && (dropped > 0 || !fields_read) {
+            return None;
+        }
+        skipped += dropped;
+
+        songs.push(Song {
+            name: text("name").unwrap_or("Untitled").to_string(),
+            bpm: number("bpm").filter(|&bpm| bpm > 0).unwrap_or(DEFAULT_BPM),
+            bits_per_page: number("bitsPerPage").unwrap_or(DEFAULT_BITS_PER_PAGE),
+            pitch_level: pitch_number
+                .or_else(|| text("pitch").and_then(pitch_level))
+                .unwrap_or(0),
+            help_text: text("helpText").unwrap_or_default().to_string(),
+            song_notes,
+        });
+    }
+    (!songs.is_empty()).then_some(CommunitySheet {
+        songs,
+        format,
+        skipped,
+    })
+}
+
+// Turns bare "Key5" notes, and notes written as the QWERTY character for the key,
+// into "1Key5"; returns whether any needed it
+pub fn normalize_keys(songs: &mut [Song]) -> bool {
+    let mut changed = false;
+    for note in songs.iter_mut().flat_map(|song| &mut song.song_notes) {
+        let mut chars = note.key.chars();
+        let fixed = if let Some(position) = note.key.strip_prefix("Key") {
+            position.parse().ok().map(|position| bank_key(0, position))
+        } else if let (Some(c), None) = (chars.next(), chars.next()) {
+            char_to_grid(c).and_then(unmap_key)
+        } else {
+            None
+        };
+        if let Some(key) = fixed {
+            note.key = key;
+            changed = true;
+        }
+    }
+    changed
+}
+
+// {"key": "1Key0", "time": 0}; the time may be written as a float
+fn object_note(value: &Value) -> Option<Note> {
+    let key = value.get("key")?.as_str()?.to_string();
+    let time = value.get("time")?.as_f64()?.max(0.0) as u64;
+    Some(Note {
+        key,
+        time,
+        duration: None,
+        velocity: None,
+    })
+}
+
+// [key index, time, layer]; the layer only matters to Sky Music Nightly itself
+fn recorded_note(value: &Value) -> Option<Note> {
+    let fields = value.as_array()?;
+    let index = fields.first()?.as_u64()?;
+    let time = fields.get(1)?.as_f64()?.max(0.0) as u64;
+    // Positions past the grid are kept so loading can warn about them
+    let key = usize::try_from(index)
+        .ok()
+        .and_then(unmap_key)
+        .unwrap_or_else(|| bank_key(0, index as i32));
+    Some(Note {
+        key,
+        time,
+        duration: None,
+        velocity: None,
+    })
+}
+
+fn pitch_level(name: &str) -> Option<i32> {
+    PITCH_NAMES
+        .iter()
+        .position(|names| names.contains(&name.trim()))
+        .map(|level| level as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::song_file::{SongError, parse_songs, validate_song};
+
+    fn keys(song: &Song) -> Vec<&str> {
+        song.song_notes
+            .iter()
+            .map(|note| note.key.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn nightly_recordings_read_their_note_arrays() {
+        let json = r#"[{"name": "Recorded", "pitch": "Db", "notes": [
+                [0, 0, 1], [4, 250.5, 1], [16, 500, 1]
+            ]}]"#;
+        let sheet = parse_community(json).unwrap();
+        assert_eq!(sheet.format, SheetFormat::NightlyRecorded);
+        assert_eq!(sheet.skipped, 0);
+        let song = &sheet.songs[0];
+        assert_eq!((song.pitch_level, song.bpm), (1, DEFAULT_BPM));
+        // Past the grid stays a sheet key so loading can warn about it
+        assert_eq!(keys(song), ["1Key0", "1Key4", "1Key16"]);
+        assert_eq!(song.song_notes[1].time, 250);
+    }
+
+    #[test]
+    fn wrapped_songs_skip_broken_notes_with_a_warning() {
+        let json = r#"{"songs": [{"name": "Wrapped", "songNotes": [
+                {"key": "1Key1", "time": 0},
+                {"key": "1Key2", "time": "soon"},
+                {"key": "1Key3", "time": 500.0}
+            ]}, "not a song"]}"#;
+        let sheet = parse_community(json).unwrap();
+        assert_eq!(sheet.format, SheetFormat::Community);
+        assert_eq!(sheet.skipped, 2);
+        assert_eq!(keys(&sheet.songs[0]), ["1Key1", "1Key3"]);
+
+        let loaded = parse_songs(json).unwrap();
+        assert_eq!(loaded.format, SheetFormat::Community);
+        assert_eq!(loaded.warnings, ["skipped 2 malformed songs or notes"]);
+    }
+
+    #[test]
+    fn bare_and_character_keys_become_sheet_keys() {
+        let json = r#"[{"name": "Bare", "songNotes": [
+                {"key": "Key5", "time": 0},
+                {"key": "y", "time": 250},
+                {"key": "2Key3", "time": 500}
+            ]}]"#;
+        let mut songs = parse_community(json).unwrap().songs;
+        assert!(normalize_keys(&mut songs));
+        assert_eq!(keys(&songs[0]), ["1Key5", "1Key0", "2Key3"]);
+        assert!(!normalize_keys(&mut songs));
+    }
+
+    #[test]
+    fn broken_sky_studio_sheets_keep_their_error() {
+        let json = r#"[{"name": "Broken", "bpm": 120, "bitsPerPage": 16, "pitchLevel": 0,
+            "helpText": "", "songNotes": [
+                {"key": "1Key0", "time": 0},
+                {"key": "1Key1", "time": "250"}
+            ]}]"#;
+        assert!(parse_community(json).is_none());
+        assert!(matches!(validate_song(json), Err(SongError::Parse(_))));
+        // The notes are fine here, but a mistyped field is just as broken
+        let bad_bpm = json
+            .replace("\"250\"", "250")
+            .replace("\"bpm\": 120", "\"bpm\": \"fast\"");
+        assert!(matches!(validate_song(&bad_bpm), Err(SongError::Parse(_))));
+    }
+}
@@ -0,0 +1,502 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::key_layout::{bank_size, grid_index, sheet_key, sheet_position};
+use crate::sheet_format::{SheetFormat, normalize_keys, parse_community};
+use crate::{Song, debug_log};
+
+// How many distinct unrecognized keys to list in a warning
+const MAX_REPORTED_KEYS: usize = 5;
+// Silences this long mid-song are more likely a bad timestamp than a rest
+const SUSPICIOUS_GAP_MS: u64 = 30_000;
+
+// Extensions sheet files are commonly saved with; the contents are the same JSON
+pub const SHEET_EXTENSIONS: &[&str] = &["txt", "json", "skysheet"];
+
+// Songs parsed from a sheet file, plus anything worth telling the user about it
+#[derive(Debug, Default)]
+pub struct LoadedSongs {
+    pub songs: Vec<Song>,
+    pub warnings: Vec<String>,
+    pub format: SheetFormat,
+}
+
+// The one way songs are read for playing, so the window, manual mode, playback and
+// the folder cleanup all accept and fix up exactly the same files
+pub fn load_songs(path: &str, dedupe: bool) -> Result<LoadedSongs, String> {
+    let mut loaded = read_song_file(path)?;
+    if dedupe {
+        let removed = dedupe_notes(&mut loaded.songs);
+        if removed > 0 {
+            debug_log::log(format!("Removed {} duplicate notes from {}", removed, path));
+            loaded
+                .warnings
+                .push(format!("removed {} duplicate notes", removed));
+        }
+    }
+    Ok(loaded)
+}
+
+// Just the songs, for playback. Automatic and manual playback both read through this,
+// so a file one of them accepts the other does too.
+pub fn load_song_from_path(path: &str) -> Result<Vec<Song>, String> {
+    read_song_file(path).map(|loaded| loaded.songs)
+}
+
+pub fn read_song_file(path: &str) -> Result<LoadedSongs, String> {
+    // MIDI files are converted on load so they work everywhere a sheet does
+    if is_midi_path(path) {
+        return crate::midi::read_midi_file(path);
+    }
+    let mut file = File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    parse_songs(&decode_text(&bytes)?)
+}
+
+// Sheets exported by some tools are UTF-16 with a byte order mark; everything else
+// is read as UTF-8
+fn decode_text(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |to_unit: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes[2..]
+            .chunks_exact(2)
+            .map(|pair| to_unit([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16(&units).map_err(|e| format!("Failed to read file: {}", e))
+    };
+    match bytes {
+        [0xFF, 0xFE, ..] => utf16(u16::from_le_bytes),
+        [0xFE, 0xFF, ..] => utf16(u16::from_be_bytes),
+        _ => String::from_utf8(bytes.to_vec())
+            .map_err(|e| format!("Failed to read file: not UTF-8 text ({})", e)),
+    }
+}
+
+// Some sources wrap the song array in an object
+#[derive(Deserialize)]
+struct SongList {
+    songs: Vec<Song>,
+}
+
+// Why a sheet file couldn't be used
+#[derive(Debug)]
+pub enum SongError {
+    NoJson,
+    // serde's message names the missing or mistyped field and where it is
+    Parse(serde_json::Error),
+    NoSongs,
+    NoNotes { song: String },
+}
+
+impl fmt::Display for SongError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoJson => write!(f, "Invalid song format! No JSON array or object found."),
+            Self::Parse(e) => write!(f, "Invalid song format! {}", e),
+            Self::NoSongs => write!(f, "Invalid song format! The file contains no songs."),
+            Self::NoNotes { song } => {
+                write!(f, "Song \"{}\" has no notes (songNotes is empty)", song)
+            }
+        }
+    }
+}
+
+// Parses and checks a sheet, so both loading and playback report the same reason.
+// Also returns how many broken songs or notes a community schema had to skip.
+pub fn validate_song(contents: &str) -> Result<(Vec<Song>, SheetFormat, usize), SongError> {
+    let (json, _) = extract_json(contents).ok_or(SongError::NoJson)?;
+
+    // Other community schemas are only tried when Sky Studio's doesn't fit, and its
+    // error is the one reported if none of them do either
+    let (mut songs, mut format, skipped) = match sky_studio_songs(json) {
+        Ok(songs) => (songs, SheetFormat::SkyStudio, 0),
+        Err(e) => {
+            let sheet = parse_community(json).ok_or(e)?;
+            (sheet.songs, sheet.format, sheet.skipped)
+        }
+    };
+    if normalize_keys(&mut songs) && format == SheetFormat::SkyStudio {
+        format = SheetFormat::Community;
+    }
+
+    if songs.is_empty() {
+        return Err(SongError::NoSongs);
+    }
+    if songs.iter().all(|song| song.song_notes.is_empty()) {
+        return Err(SongError::NoNotes {
+            song: songs[0].name.clone(),
+        });
+    }
+    Ok((songs, format, skipped))
+}
+
+fn sky_studio_songs(json: &str) -> Result<Vec<Song>, SongError> {
+    // Most sheets are an array of songs, but a lone song object is accepted too, as is
+    // an object holding the array in "songs"
+    let songs = if json.starts_with('[') {
+        serde_json::from_str::<Vec<Song>>(json).map_err(SongError::Parse)?
+    } else {
+        match serde_json::from_str::<Song>(json) {
+            Ok(song) => vec![song],
+            // The lone song's error says more about what's wrong with most files
+            Err(e) => serde_json::from_str::<SongList>(json)
+                .map(|list| list.songs)
+                .map_err(|_| SongError::Parse(e))?,
+        }
+    };
+    Ok(songs)
+}
+
+// Whether the player can open this file, judging by its extension
+pub fn is_song_path(path: &str) -> bool {
+    is_sheet_path(path) || is_midi_path(path)
+}
+
+pub fn is_sheet_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            SHEET_EXTENSIONS
+                .iter()
+                .any(|sheet_ext| ext.eq_ignore_ascii_case(sheet_ext))
+        })
+}
+
+pub fn is_midi_path(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("mid") || ext.eq_ignore_ascii_case("midi"))
+}
+
+// Writes songs in the same array format the player reads
+pub fn write_song_file(path: &str, songs: &[Song]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(songs)
+        .map_err(|e| format!("Failed to serialize song: {}", e))?;
+    let mut file = File::create(path).map_err(|e| format!("Failed to create file: {}", e))?;
+    file.write_all(json.as_bytes())
+        .map_err(|e| format!("Failed to write file: {}", e))
+}
+
+// Outcome of checking every sheet in a folder
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub ok: usize,
+    // File name and why it couldn't be read
+    pub failed: Vec<(String, String)>,
+    // File name and what loading it had to fix or skip
+    pub fixed: Vec<(String, Vec<String>)>,
+    pub rewritten: usize,
+}
+
+// Parses every sheet in a folder (not its subfolders). With `rewrite`, each one that
+// parses is saved back sorted and pretty-printed; files that fail are left alone.
+pub fn normalize_folder(dir: &Path, rewrite: bool, dedupe: bool) -> Result<BatchReport, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read folder: {}", e))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .map(|path| path.display().to_string())
+        .filter(|path| is_sheet_path(path))
+        .collect();
+    paths.sort();
+
+    let mut report = BatchReport::default();
+    for path in paths {
+        let name = Path::new(&path)
+            .file_name()
+            .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+        match load_songs(&path, dedupe) {
+            Ok(loaded) => {
+                if rewrite {
+                    if let Err(e) = write_song_file(&path, &loaded.songs) {
+                        report.failed.push((name, e));
+                        continue;
+                    }
+                    report.rewritten += 1;
+                }
+                report.ok += 1;
+                if !loaded.warnings.is_empty() {
+                    report.fixed.push((name, loaded.warnings));
+                }
+            }
+            Err(e) => report.failed.push((name, e)),
+        }
+    }
+    Ok(report)
+}
+
+pub fn parse_songs(contents: &str) -> Result<LoadedSongs, String> {
+    // A byte order mark isn't text outside the JSON worth a warning
+    let contents = contents.trim_start_matches('\u{feff}');
+    let (songs, format, skipped) = validate_song(contents).map_err(|e| e.to_string())?;
+    let mut loaded = LoadedSongs {
+        songs,
+        warnings: Vec::new(),
+        format,
+    };
+
+    if skipped > 0 {
+        loaded
+            .warnings
+            .push(format!("skipped {} malformed songs or notes", skipped));
+    }
+
+    if extract_json(contents).is_some_and(|(_, trimmed)| trimmed) {
+        loaded
+            .warnings
+            .push("ignored text outside the song JSON".to_string());
+    }
+
+    // Playback assumes time never goes backwards; a stable sort keeps chord members in order
+    let mut reordered = 0;
+    for song in &mut loaded.songs {
+        if !song.song_notes.is_sorted_by_key(|note| note.time) {
+            song.song_notes.sort_by_key(|note| note.time);
+            reordered += 1;
+        }
+    }
+    if reordered > 0 {
+        loaded.warnings.push(if loaded.songs.len() == 1 {
+            "notes were out of order and have been sorted".to_string()
+        } else {
+            format!("sorted out-of-order notes in {} songs", reordered)
+        });
+    }
+
+    for song in &loaded.songs {
+        let longest_gap = song
+            .song_notes
+            .windows(2)
+            .enumerate()
+            .map(|(i, pair)| (i + 1, pair[1].time - pair[0].time))
+            .max_by_key(|&(_, gap)| gap);
+        if let Some((index, gap)) = longest_gap
+            && gap >= SUSPICIOUS_GAP_MS
+        {
+            loaded.warnings.push(format!(
+                "\"{}\" has a suspicious {} s gap before note {}",
+                song.name,
+                gap / 1000,
+                index + 1
+            ));
+        }
+    }
+
+    // Playback skips notes it can't place on the grid, so say which ones up front
+    let mut unmapped = 0;
+    let mut out_of_range = 0;
+    let mut keyless = 0;
+    let mut unknown_keys: Vec<&str> = Vec::new();
+    for note in loaded.songs.iter().flat_map(|song| &song.song_notes) {
+        if let Some(index) = grid_index(&note.key) {
+            // On the grid, but in a bank with no key for that position
+            if sheet_key(&note.key).is_some_and(|(bank, _)| index >= bank_size(bank)) {
+                keyless += 1;
+            }
+            continue;
+        }
+        if sheet_position(&note.key).is_some() {
+            // Written for a wider instrument; transposing can bring these in
+            out_of_range += 1;
+        } else {
+            unmapped += 1;
+            if !unknown_keys.contains(&note.key.as_str()) {
+                unknown_keys.push(&note.key);
+            }
+        }
+    }
+    if unmapped > 0 {
+        let mut listed = unknown_keys
+            .iter()
+            .take(MAX_REPORTED_KEYS)
+            .map(|key| format!("{:?}", key))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if unknown_keys.len() > MAX_REPORTED_KEYS {
+            listed.push_str(&format!(
+                " and {} more",
+                unknown_keys.len() - MAX_REPORTED_KEYS
+            ));
+        }
+        loaded.warnings.push(format!(
+            "{} notes could not be mapped and were skipped; unrecognized keys: {}",
+            unmapped, listed
+        ));
+    }
+    if out_of_range > 0 {
+        loaded.warnings.push(format!(
+            "{} notes are beyond the 15 keys; try Fit to range",
+            out_of_range
+        ));
+    }
+    if keyless > 0 {
+        loaded.warnings.push(format!(
+            "{} notes are on bank positions with no key and will be skipped",
+            keyless
+        ));
+    }
+
+    Ok(loaded)
+}
+
+// Drops notes repeating the key and time of an earlier one, which would press the
+// same key twice at once. Expects notes sorted by time; returns how many went.
+pub fn dedupe_notes(songs: &mut [Song]) -> usize {
+    let mut removed = 0;
+    for song in songs {
+        let before = song.song_notes.len();
+        let mut time = None;
+        let mut chord: Vec<String> = Vec::new();
+        song.song_notes.retain(|note| {
+            if time != Some(note.time) {
+                time = Some(note.time);
+                chord.clear();
+            }
+            if chord.contains(&note.key) {
+                return false;
+            }
+            chord.push(note.key.clone());
+            true
+        });
+        removed += before - song.song_notes.len();
+    }
+    removed
+}
+
+// Finds the outermost JSON array/object, skipping comments or markdown fences around it.
+// The flag reports whether anything besides whitespace was cut away.
+fn extract_json(contents: &str) -> Option<(&str, bool)> {
+    let start = contents.find(['[', '{'])?;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut end = None;
+
+    for (offset, c) in contents[start..].char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => depth += 1,
+            ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(start + offset + c.len_utf8());
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let end = end?;
+    let trimmed = !contents[..start].trim().is_empty() || !contents[end..].trim().is_empty();
+    Some((&contents[start..end], trimmed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::song;
+
+    #[test]
+    fn shuffled_notes_are_sorted_with_a_warning() {
+        let json = r#"[{"name": "Shuffled", "bpm": 120, "bitsPerPage": 16, "pitchLevel": 0,
+            "helpText": "", "songNotes": [
+                {"key": "1Key3", "time": 500},
+                {"key": "1Key0", "time": 0},
+                {"key": "1Key5", "time": 250},
+                {"key": "1Key1", "time": 250},
+                {"key": "1Key2", "time": 750}
+            ]}]"#;
+        let loaded = parse_songs(json).unwrap();
+        let notes: Vec<(u64, &str)> = loaded.songs[0]
+            .song_notes
+            .iter()
+            .map(|note| (note.time, note.key.as_str()))
+            .collect();
+        // The chord at 250 keeps the order it was written in
+        assert_eq!(
+            notes,
+            [
+                (0, "1Key0"),
+                (250, "1Key5"),
+                (250, "1Key1"),
+                (500, "1Key3"),
+                (750, "1Key2")
+            ]
+        );
+        assert_eq!(
+            loaded.warnings,
+            ["notes were out of order and have been sorted"]
+        );
+    }
+
+    #[test]
+    fn duplicate_notes_collapse_to_one() {
+        let mut songs = [song(&[
+            (0, 0),
+            (0, 4),
+            (0, 0),
+            (250, 0),
+            (250, 4),
+            (250, 4),
+        ])];
+        assert_eq!(dedupe_notes(&mut songs), 2);
+        let notes: Vec<(u64, &str)> = songs[0]
+            .song_notes
+            .iter()
+            .map(|note| (note.time, note.key.as_str()))
+            .collect();
+        // The same key at a different time is a new note, not a duplicate
+        assert_eq!(
+            notes,
+            [(0, "1Key0"), (0, "1Key4"), (250, "1Key0"), (250, "1Key4")]
+        );
+    }
+
+    #[test]
+    fn long_silences_are_flagged() {
+        let json = r#"{"name": "Gappy", "bpm": 120, "bitsPerPage": 16, "pitchLevel": 0,
+            "helpText": "", "songNotes": [
+                {"key": "1Key0", "time": 0},
+                {"key": "1Key1", "time": 500},
+                {"key": "1Key2", "time": 45500}
+            ]}"#;
+        let loaded = parse_songs(json).unwrap();
+        assert_eq!(
+            loaded.warnings,
+            ["\"Gappy\" has a suspicious 45 s gap before note 3"]
+        );
+    }
+
+    #[test]
+    fn bank_positions_without_a_key_are_flagged() {
+        let json = r#"{"name": "Banks", "bpm": 120, "bitsPerPage": 16, "pitchLevel": 0,
+            "helpText": "", "songNotes": [
+                {"key": "2Key14", "time": 0},
+                {"key": "3Key12", "time": 250},
+                {"key": "3Key14", "time": 500}
+            ]}"#;
+        let loaded = parse_songs(json).unwrap();
+        assert_eq!(
+            loaded.warnings,
+            ["1 notes are on bank positions with no key and will be skipped"]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+// Helpers shared by the unit tests
+use std::path::{Path, PathBuf};
+
+use crate::{Note, Song, unmap_key};
+
+// Scratch directory unique to one test, removed again when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(test: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("sky_sheet_player_{}_{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// A song with one note per (time, grid position)
+pub fn song(notes: &[(u64, usize)]) -> Song {
+    Song {
+        name: "Test".to_string(),
+        bpm: 120,
+        bits_per_page: 16,
+        pitch_level: 0,
+        help_text: String::new(),
+        song_notes: notes
+            .iter()
+            .map(|&(time, index)| Note {
+                key: unmap_key(index).unwrap(),
+                time,
+                duration: None,
+                velocity: None,
+            })
+            .collect(),
+    }
+}
@@ -0,0 +1,86 @@
+use enum_iterator::Sequence;
+use serde::{Deserialize, Serialize};
+
+// Articulation parameters used when pressing notes in automatic playback
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimingProfile {
+    pub hold_important_ms: u64,
+    pub hold_peak_ms: u64,
+    pub hold_normal_ms: u64,
+    pub gap_important_ms: u64,
+    pub gap_normal_ms: u64,
+    pub variation_ms: u64,
+}
+
+impl Default for TimingProfile {
+    fn default() -> Self {
+        Self {
+            hold_important_ms: 55,
+            hold_peak_ms: 50,
+            hold_normal_ms: 35,
+            gap_important_ms: 5,
+            gap_normal_ms: 10,
+            variation_ms: 5,
+        }
+    }
+}
+
+impl TimingProfile {
+    // Every note gets the normal hold and gap with no randomness, for literal playback
+    pub fn literal(self) -> Self {
+        Self {
+            hold_important_ms: self.hold_normal_ms,
+            hold_peak_ms: self.hold_normal_ms,
+            gap_important_ms: self.gap_normal_ms,
+            variation_ms: 0,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, Sequence)]
+pub enum InstrumentPreset {
+    #[default]
+    Piano,
+    Harp,
+    Bells,
+    Custom,
+}
+
+impl InstrumentPreset {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Piano => "Piano",
+            Self::Harp => "Harp",
+            Self::Bells => "Bells",
+            Self::Custom => "Custom",
+        }
+    }
+
+    // Fixed timing for the preset; Custom has none and uses the user's values
+    pub fn profile(self) -> Option<TimingProfile> {
+        match self {
+            Self::Piano => Some(TimingProfile::default()),
+            // Harp notes ring, so longer legato holds sound natural
+            Self::Harp => Some(TimingProfile {
+                hold_important_ms: 70,
+                hold_peak_ms: 65,
+                hold_normal_ms: 50,
+                gap_important_ms: 5,
+                gap_normal_ms: 8,
+                variation_ms: 6,
+            }),
+            // Bells want short, crisp strikes with little drift
+            Self::Bells => Some(TimingProfile {
+                hold_important_ms: 40,
+                hold_peak_ms: 35,
+                hold_normal_ms: 25,
+                gap_important_ms: 10,
+                gap_normal_ms: 15,
+                variation_ms: 2,
+            }),
+            Self::Custom => None,
+        }
+    }
+}
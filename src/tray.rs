@@ -0,0 +1,148 @@
+// Tray icon with the playback controls, so the window can be hidden entirely while
+// the global hotkeys keep working
+
+use eframe::egui;
+use std::sync::{Arc, Mutex};
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::{Icon, MouseButton, MouseButtonState, TrayIcon, TrayIconBuilder, TrayIconEvent};
+
+use crate::hotkey_utils::HotkeyCapture;
+use crate::{AppState, stop_playback, toggle_play_pause};
+
+// Menu item ids, matched when an item is picked
+const SHOW_ID: &str = "show";
+const PLAY_PAUSE_ID: &str = "play_pause";
+const STOP_ID: &str = "stop";
+// The icon is drawn rather than loaded, so there's no image file to ship
+const ICON_SIZE: u32 = 32;
+const ICON_COLOR: [u8; 3] = [90, 110, 220];
+
+// Keeps the icon in the tray; dropping it removes the icon. On Linux the icon lives
+// on its own GTK thread instead, for as long as the app runs.
+pub struct Tray {
+    #[cfg(not(target_os = "linux"))]
+    _icon: TrayIcon,
+}
+
+// Puts the icon in the tray and routes its clicks and menu picks to the app
+pub fn create(state_arc: &Arc<Mutex<AppState>>, ctx: &egui::Context) -> Result<Tray, String> {
+    set_handlers(Arc::clone(state_arc), ctx.clone());
+    #[cfg(target_os = "linux")]
+    {
+        spawn_gtk_icon()?;
+        Ok(Tray {})
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        // Windows and macOS serve the icon from the window's own event loop
+        Ok(Tray {
+            _icon: build_icon()?,
+        })
+    }
+}
+
+// tray-icon needs a GTK main loop on Linux, and the icon has to be made on its thread
+#[cfg(target_os = "linux")]
+fn spawn_gtk_icon() -> Result<(), String> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        if let Err(e) = gtk::init() {
+            let _ = sender.send(Err(format!("Failed to start GTK: {}", e)));
+            return;
+        }
+        match build_icon() {
+            Ok(_icon) => {
+                let _ = sender.send(Ok(()));
+                gtk::main();
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e));
+            }
+        }
+    });
+    receiver
+        .recv()
+        .unwrap_or_else(|_| Err("The tray thread exited".to_string()))
+}
+
+fn build_icon() -> Result<TrayIcon, String> {
+    let menu = Menu::with_items(&[
+        &MenuItem::with_id(SHOW_ID, "Show window", true, None),
+        &PredefinedMenuItem::separator(),
+        &MenuItem::with_id(PLAY_PAUSE_ID, "Play/Pause", true, None),
+        &MenuItem::with_id(STOP_ID, "Stop", true, None),
+    ])
+    .map_err(|e| format!("Failed to build the tray menu: {}", e))?;
+    let icon = Icon::from_rgba(icon_pixels(), ICON_SIZE, ICON_SIZE)
+        .map_err(|e| format!("Failed to build the tray icon: {}", e))?;
+    TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        // Left click restores the window; the menu is on right click
+        .with_menu_on_left_click(false)
+        .with_tooltip("Sky Sheet Player")
+        .with_icon(icon)
+        .build()
+        .map_err(|e| format!("Failed to create the tray icon: {}", e))
+}
+
+// The handlers run on whichever thread serves the icon, so they only touch the
+// shared state and ask the window to redraw
+fn set_handlers(state_arc: Arc<Mutex<AppState>>, ctx: egui::Context) {
+    let click_ctx = ctx.clone();
+    // Linux never reports clicks on the icon itself, hence "Show window" in the menu
+    TrayIconEvent::set_event_handler(Some(move |event| {
+        if let TrayIconEvent::Click {
+            button: MouseButton::Left,
+            button_state: MouseButtonState::Up,
+            ..
+        } = event
+        {
+            show_window(&click_ctx);
+        }
+    }));
+    MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+        match event.id.as_ref() {
+            SHOW_ID => show_window(&ctx),
+            // Like the hotkey, Play/Pause does nothing until a song is loaded; like the
+            // window's buttons, neither works while a hotkey is being set
+            PLAY_PAUSE_ID => {
+                let mut state = state_arc.lock().unwrap();
+                if state.song_path.is_some() && state.hotkey_capture == HotkeyCapture::None {
+                    toggle_play_pause(&mut state, &state_arc);
+                }
+            }
+            STOP_ID => {
+                let mut state = state_arc.lock().unwrap();
+                if state.hotkey_capture == HotkeyCapture::None {
+                    stop_playback(&mut state);
+                }
+            }
+            _ => {}
+        }
+        ctx.request_repaint();
+    }));
+}
+
+fn show_window(ctx: &egui::Context) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+    ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
+    ctx.request_repaint();
+}
+
+// A filled circle on a transparent square
+fn icon_pixels() -> Vec<u8> {
+    let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+    let radius = ICON_SIZE as f32 / 2.0 - 1.0;
+    let mut pixels = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+    for y in 0..ICON_SIZE {
+        for x in 0..ICON_SIZE {
+            let distance = (x as f32 - center).hypot(y as f32 - center);
+            // Half a pixel of fade keeps the edge from looking jagged
+            let alpha = (radius + 0.5 - distance).clamp(0.0, 1.0);
+            pixels.extend_from_slice(&ICON_COLOR);
+            pixels.push((alpha * 255.0) as u8);
+        }
+    }
+    pixels
+}
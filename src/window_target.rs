@@ -0,0 +1,63 @@
+// Focuses the game window before playback so keystrokes land in the right place
+
+// Brings the first visible top-level window whose title contains `title` to the foreground
+#[cfg(windows)]
+pub fn focus_window(title: &str) -> Result<(), String> {
+    use windows_sys::Win32::Foundation::{BOOL, HWND, LPARAM};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowTextW, IsIconic, IsWindowVisible, SW_RESTORE, SetForegroundWindow,
+        ShowWindow,
+    };
+
+    struct Search {
+        needle: String,
+        found: HWND,
+    }
+
+    unsafe extern "system" fn visit(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = unsafe { &mut *(lparam as *mut Search) };
+        if unsafe { IsWindowVisible(hwnd) } == 0 {
+            return 1;
+        }
+        let mut buf = [0u16; 512];
+        let len = unsafe { GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as i32) };
+        if len <= 0 {
+            return 1;
+        }
+        let window_title = String::from_utf16_lossy(&buf[..len as usize]).to_lowercase();
+        if window_title.contains(&search.needle) {
+            search.found = hwnd;
+            return 0; // Stop enumerating
+        }
+        1
+    }
+
+    let mut search = Search {
+        needle: title.to_lowercase(),
+        found: std::ptr::null_mut(),
+    };
+    unsafe {
+        EnumWindows(Some(visit), &mut search as *mut Search as LPARAM);
+    }
+    if search.found.is_null() {
+        return Err(format!("No window matching \"{}\" was found", title));
+    }
+
+    unsafe {
+        if IsIconic(search.found) != 0 {
+            ShowWindow(search.found, SW_RESTORE);
+        }
+        if SetForegroundWindow(search.found) == 0 {
+            return Err(format!("Could not bring \"{}\" to the foreground", title));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+pub fn focus_window(title: &str) -> Result<(), String> {
+    Err(format!(
+        "Focusing \"{}\" by title is only supported on Windows",
+        title
+    ))
+}